@@ -0,0 +1,524 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::{de::IntoDeserializer, Deserialize};
+use std::{env, fmt, fs, path::Path};
+use toml::{
+    de::{DeTable, DeValue},
+    Spanned, Table, Value,
+};
+
+use crate::{git::backend::GitBackend, terminal::types::Shell};
+
+use super::{
+    conditional::{glob_matcher, BranchPattern},
+    env_key_path, git_config_table, global_config_file, is_map_entry, is_trusted_key,
+    load_config_layers, lookup, merge_tables, nested_table, parse_value, read_file,
+    repo_config_files, resolve_config, trusts_repo_config,
+    types::Config,
+    work_tree_config_file, ConfigOptions, DEPRECATED_KEYS, ENV_PREFIX, GIT_CONFIG_SECTION,
+    MAP_KEYS,
+};
+
+const CONDITION_KEYS: &[&str] = &["gitdir", "remote_url", "branch"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub source: String,
+    /// 1-based line and column
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)?;
+        if let Some((line, column)) = self.position {
+            write!(f, ":{line}:{column}")?;
+        }
+        match self.severity {
+            Severity::Warning => write!(f, ": warning: {}", self.message),
+            Severity::Error => write!(f, ": error: {}", self.message),
+        }
+    }
+}
+
+/// Checks every configuration source `get_app_config` would read for syntax
+/// errors, unknown or deprecated keys and invalid values.
+pub fn check_app_config(
+    config_file: Option<&Path>,
+    mut backend: Option<&mut dyn GitBackend>,
+) -> Result<Vec<Diagnostic>> {
+    // Palette colors and custom segments may be defined in any source, all
+    // their names are reported as undefined if the configuration can not be
+    // loaded.
+    let options = ConfigOptions {
+        config_file,
+        ..Default::default()
+    };
+    let maps = load_config_layers(options, backend.as_mut().map(|backend| &mut **backend as _))
+        .ok()
+        .map(|layers| {
+            layers
+                .config
+                .into_iter()
+                .filter(|(key, _)| MAP_KEYS.contains(&key.as_str()))
+                .collect()
+        });
+    let defaults = Table::try_from(Config::default())?;
+    let mut checker = ConfigChecker {
+        schema: schema_with_shell_sections(&defaults),
+        defaults,
+        maps,
+        diagnostics: Vec::new(),
+    };
+
+    let trusted_file = config_file
+        .map(Path::to_path_buf)
+        .or_else(global_config_file);
+    if let Some(trusted_file) = &trusted_file {
+        checker.check_file(trusted_file, config_file.is_some(), true);
+    }
+    let trust_repo_config = trusts_repo_config(
+        &trusted_file
+            .and_then(|trusted_file| read_file(&trusted_file).ok())
+            .unwrap_or_default(),
+    );
+    if let Some(backend) = backend {
+        if !trust_repo_config {
+            if let Some(work_tree_file) =
+                work_tree_config_file(backend)?.filter(|file| file.exists())
+            {
+                checker.report(
+                    Severity::Warning,
+                    &work_tree_file.display().to_string(),
+                    None,
+                    "ignored, `trust_repo_config` is not set".into(),
+                );
+            }
+        }
+        for config_file in repo_config_files(backend, trust_repo_config)? {
+            checker.check_file(&config_file, false, false);
+        }
+        checker.check_git_config(backend.config_entries(GIT_CONFIG_SECTION)?);
+    }
+    checker.check_env();
+
+    Ok(checker.diagnostics)
+}
+
+struct ConfigChecker {
+    /// The known keys, the defaults with a copy in each shell section.
+    schema: Table,
+    defaults: Table,
+    /// The `MAP_KEYS` tables of the loaded configuration.
+    maps: Option<Table>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// A TOML document being checked, used to resolve spans to positions.
+struct Document<'a> {
+    source: String,
+    content: &'a str,
+    /// The `TRUSTED_KEYS` are read from this document.
+    trusted: bool,
+}
+
+impl Document<'_> {
+    fn position(&self, offset: usize) -> (usize, usize) {
+        let before = &self.content[..offset.min(self.content.len())];
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+
+        (
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count() + 1,
+        )
+    }
+
+    fn value<'i>(&self, value: &Spanned<DeValue<'i>>) -> Option<Value> {
+        Value::deserialize(value.clone().into_deserializer()).ok()
+    }
+}
+
+impl ConfigChecker {
+    fn report(
+        &mut self,
+        severity: Severity,
+        source: &str,
+        position: Option<(usize, usize)>,
+        message: String,
+    ) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            source: source.into(),
+            position,
+            message,
+        });
+    }
+
+    fn report_untrusted(&mut self, source: &str, position: Option<(usize, usize)>, display: &str) {
+        self.report(
+            Severity::Error,
+            source,
+            position,
+            format!(
+                "`{display}` is ignored, it is only read from the global configuration file and the environment"
+            ),
+        );
+    }
+
+    fn check_file(&mut self, config_file: &Path, required: bool, trusted: bool) {
+        let source = config_file.display().to_string();
+        if !config_file.exists() {
+            if required {
+                self.report(Severity::Error, &source, None, "file not found".into());
+            }
+            return;
+        }
+        let content = match fs::read_to_string(config_file) {
+            Ok(content) => content,
+            Err(err) => {
+                self.report(Severity::Error, &source, None, err.to_string());
+                return;
+            }
+        };
+        let document = Document {
+            source,
+            content: &content,
+            trusted,
+        };
+
+        match DeTable::parse(&content) {
+            Ok(table) => {
+                let first = self.diagnostics.len();
+                let schema = self.schema.clone();
+                self.check_table(&document, table.get_ref(), &schema, &[], "");
+                self.diagnostics[first..].sort_by_key(|diagnostic| diagnostic.position);
+            }
+            Err(err) => {
+                let position = err.span().map(|span| document.position(span.start));
+                self.report(
+                    Severity::Error,
+                    &document.source,
+                    position,
+                    err.message().into(),
+                );
+            }
+        }
+    }
+
+    fn check_table(
+        &mut self,
+        document: &Document,
+        table: &DeTable,
+        schema: &Table,
+        keys: &[String],
+        display_prefix: &str,
+    ) {
+        for (key, value) in table {
+            let key_name = key.get_ref().to_string();
+            let mut path = keys.to_vec();
+            path.push(key_name.clone());
+            let display = format!("{display_prefix}{}", path.join("."));
+            let position = Some(document.position(key.span().start));
+
+            if keys.is_empty() {
+                if let Some((_, replacement)) = DEPRECATED_KEYS
+                    .iter()
+                    .find(|(deprecated, _)| *deprecated == key_name)
+                {
+                    self.report(
+                        Severity::Warning,
+                        &document.source,
+                        position,
+                        format!("`{display}` is deprecated, use `{replacement}` instead"),
+                    );
+                    continue;
+                }
+                if key_name == "conditional" && display_prefix.is_empty() {
+                    self.check_conditionals(document, value);
+                    continue;
+                }
+            }
+
+            if !document.trusted && is_trusted_key(&path) {
+                self.report_untrusted(&document.source, position, &display);
+                continue;
+            }
+
+            match (schema.get(&key_name), value.get_ref()) {
+                (None, _) if is_map_entry(&path) => {
+                    self.check_document_value(document, value, position, &path, &display)
+                }
+                (None, _) if keys == ["shell"] => self.report(
+                    Severity::Error,
+                    &document.source,
+                    position,
+                    format!("unknown shell `{key_name}`"),
+                ),
+                (None, _) => self.report(
+                    Severity::Warning,
+                    &document.source,
+                    position,
+                    format!("unknown key `{display}`"),
+                ),
+                (Some(Value::Table(schema)), DeValue::Table(table)) => {
+                    self.check_table(document, table, schema, &path, display_prefix)
+                }
+                (Some(Value::Table(_)), _) => self.report(
+                    Severity::Error,
+                    &document.source,
+                    position,
+                    format!("`{display}` must be a table"),
+                ),
+                (Some(_), _) => {
+                    self.check_document_value(document, value, position, &path, &display)
+                }
+            }
+        }
+    }
+
+    fn check_document_value(
+        &mut self,
+        document: &Document,
+        value: &Spanned<DeValue>,
+        position: Option<(usize, usize)>,
+        path: &[String],
+        display: &str,
+    ) {
+        match document.value(value) {
+            Some(value) => self.check_value(&document.source, position, path, display, value),
+            None => self.report(
+                Severity::Error,
+                &document.source,
+                position,
+                format!("invalid value for `{display}`"),
+            ),
+        }
+    }
+
+    fn check_conditionals(&mut self, document: &Document, value: &Spanned<DeValue>) {
+        let DeValue::Array(conditionals) = value.get_ref() else {
+            self.report(
+                Severity::Error,
+                &document.source,
+                Some(document.position(value.span().start)),
+                "`conditional` must be an array of tables".into(),
+            );
+            return;
+        };
+
+        for (index, conditional) in conditionals.iter().enumerate() {
+            let position = Some(document.position(conditional.span().start));
+            let DeValue::Table(conditional) = conditional.get_ref() else {
+                self.report(
+                    Severity::Error,
+                    &document.source,
+                    position,
+                    format!("`conditional[{index}]` must be a table"),
+                );
+                continue;
+            };
+
+            for (key, value) in conditional {
+                let key_name = key.get_ref().as_ref();
+                let position = Some(document.position(key.span().start));
+                let display = format!("conditional[{index}].{key_name}");
+
+                match (key_name, value.get_ref()) {
+                    ("config", DeValue::Table(table)) => {
+                        let schema = self.schema.clone();
+                        let untrusted = Document {
+                            source: document.source.clone(),
+                            content: document.content,
+                            trusted: false,
+                        };
+                        self.check_table(
+                            &untrusted,
+                            table,
+                            &schema,
+                            &[],
+                            &format!("conditional[{index}].config."),
+                        );
+                    }
+                    (key_name, DeValue::String(pattern)) if CONDITION_KEYS.contains(&key_name) => {
+                        let result = match key_name {
+                            "branch" => BranchPattern::try_from(pattern.to_string()).map(|_| ()),
+                            _ => glob_matcher(pattern)
+                                .map(|_| ())
+                                .map_err(|err| err.to_string()),
+                        };
+                        if let Err(err) = result {
+                            self.report(
+                                Severity::Error,
+                                &document.source,
+                                position,
+                                format!("invalid pattern for `{display}`: {err}"),
+                            );
+                        }
+                    }
+                    (key_name, _) if key_name == "config" || CONDITION_KEYS.contains(&key_name) => {
+                        self.report(
+                            Severity::Error,
+                            &document.source,
+                            position,
+                            format!("invalid value for `{display}`"),
+                        )
+                    }
+                    _ => self.report(
+                        Severity::Warning,
+                        &document.source,
+                        position,
+                        format!("unknown key `{display}`"),
+                    ),
+                }
+            }
+        }
+    }
+
+    fn check_git_config(&mut self, entries: Vec<(String, String)>) {
+        let table = git_config_table(entries);
+        self.check_flat_table(&table, &[], "git config", |path| {
+            format!("{GIT_CONFIG_SECTION}.{}", path.join(".").replace('_', "-"))
+        });
+    }
+
+    /// Checks the git config, which never sets the `TRUSTED_KEYS`.
+    fn check_flat_table<F>(&mut self, table: &Table, keys: &[String], source: &str, display: F)
+    where
+        F: Fn(&[String]) -> String + Copy,
+    {
+        for (key, value) in table {
+            let mut path = keys.to_vec();
+            path.push(key.clone());
+
+            if is_trusted_key(&path) {
+                self.report_untrusted(source, None, &display(&path));
+                continue;
+            }
+
+            match (lookup(&self.schema, &path), value) {
+                (None, value) if is_map_entry(&path) => {
+                    self.check_value(source, None, &path, &display(&path), value.clone())
+                }
+                (None, _) if keys == ["shell"] => self.report(
+                    Severity::Error,
+                    source,
+                    None,
+                    format!("unknown shell `{key}`"),
+                ),
+                (Some(Value::Table(_)), Value::Table(table)) => {
+                    self.check_flat_table(table, &path, source, display)
+                }
+                (Some(Value::Table(_)), _) | (None, _) => self.report(
+                    Severity::Warning,
+                    source,
+                    None,
+                    format!("unknown key `{}`", display(&path)),
+                ),
+                (Some(_), value) => {
+                    self.check_value(source, None, &path, &display(&path), value.clone())
+                }
+            }
+        }
+    }
+
+    fn check_env(&mut self) {
+        let mut vars: Vec<(String, String)> = env::vars().collect();
+        vars.sort();
+
+        for (name, value) in vars {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            if key == "CONFIG" {
+                continue;
+            }
+            match env_key_path(&self.defaults, &key.to_ascii_lowercase()) {
+                Some(path) => {
+                    let source = format!("env {name}");
+                    self.check_value(&source, None, &path, &name, parse_value(&value));
+                }
+                None => self.report(
+                    Severity::Warning,
+                    &format!("env {name}"),
+                    None,
+                    "does not match any config key".into(),
+                ),
+            }
+        }
+    }
+
+    /// Deserializes the defaults with only this value overridden to find
+    /// invalid colors, types and the like.
+    fn check_value(
+        &mut self,
+        source: &str,
+        position: Option<(usize, usize)>,
+        path: &[String],
+        display: &str,
+        value: Value,
+    ) {
+        if let Value::String(string) = &value {
+            if string.chars().any(char::is_control) {
+                self.report(
+                    Severity::Warning,
+                    source,
+                    position,
+                    format!("`{display}` contains control characters"),
+                );
+            }
+        }
+
+        let Some((key, sections)) = path.split_last() else {
+            return;
+        };
+        let mut overrides = Table::new();
+        if let Some(table) = nested_table(&mut overrides, sections.to_vec()) {
+            table.insert(key.clone(), value);
+        }
+
+        let mut config = self.defaults.clone();
+        if let Some(maps) = &self.maps {
+            config.extend(maps.clone());
+        }
+        merge_tables(&mut config, overrides, "", &mut |_, _| ());
+        if let Err(err) = resolve_config(&mut config) {
+            self.report(
+                Severity::Error,
+                source,
+                position,
+                format!("invalid value for `{display}`: {}", err.root_cause()),
+            );
+            return;
+        }
+        if let Err(err) = config.try_into::<Config>() {
+            self.report(
+                Severity::Error,
+                source,
+                position,
+                format!("invalid value for `{display}`: {}", err.message()),
+            );
+        }
+    }
+}
+
+fn schema_with_shell_sections(defaults: &Table) -> Table {
+    let mut section = defaults.clone();
+    section.remove("shell");
+    section.remove("conditional");
+    let sections = Shell::value_variants()
+        .iter()
+        .map(|shell| (shell.name(), Value::Table(section.clone())))
+        .collect();
+
+    let mut schema = defaults.clone();
+    schema.insert("shell".into(), Value::Table(sections));
+    schema
+}
@@ -0,0 +1,223 @@
+use anyhow::Result;
+use globset::{GlobBuilder, GlobMatcher};
+use regex_automata::meta::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs,
+    path::{self, Path, PathBuf},
+};
+
+use crate::git::backend::GitBackend;
+
+use super::types::ConditionalConfig;
+
+/// The properties of the discovered repository conditional sections are
+/// matched against.
+pub struct RepoContext {
+    git_dirs: Vec<PathBuf>,
+    branch: String,
+    remote_urls: Vec<String>,
+}
+
+impl RepoContext {
+    pub fn new(backend: &mut dyn GitBackend) -> Result<RepoContext> {
+        let git_dir = path::absolute(backend.git_dir()?)?;
+        let mut git_dirs = vec![git_dir.components().collect()];
+        if let Ok(real_git_dir) = fs::canonicalize(&git_dir) {
+            git_dirs.push(real_git_dir);
+        }
+        let remote_urls = backend
+            .config_entries("remote")?
+            .into_iter()
+            .filter(|(name, _)| name.ends_with(".url"))
+            .map(|(_, url)| url)
+            .collect();
+
+        Ok(RepoContext {
+            git_dirs,
+            branch: backend.local_branch()?,
+            remote_urls,
+        })
+    }
+}
+
+impl ConditionalConfig {
+    pub fn matches(&self, context: &RepoContext) -> Result<bool> {
+        if let Some(gitdir) = &self.gitdir {
+            let matcher = glob_matcher(&gitdir_pattern(gitdir))?;
+            if !context
+                .git_dirs
+                .iter()
+                .any(|git_dir| matcher.is_match(git_dir))
+            {
+                return Ok(false);
+            }
+        }
+        if let Some(remote_url) = &self.remote_url {
+            let matcher = glob_matcher(remote_url)?;
+            if !context.remote_urls.iter().any(|url| matcher.is_match(url)) {
+                return Ok(false);
+            }
+        }
+        if let Some(branch) = &self.branch {
+            if !branch.is_match(&context.branch) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// A glob or, enclosed in slashes, a regular expression matched against the
+/// whole branch name, e.g. `release/*` or `/hotfix-.*/`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct BranchPattern {
+    source: String,
+    matcher: BranchMatcher,
+}
+
+#[derive(Debug, Clone)]
+enum BranchMatcher {
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+impl BranchPattern {
+    /// Never matches a detached HEAD (empty branch name).
+    pub fn is_match(&self, branch: &str) -> bool {
+        if branch.is_empty() {
+            return false;
+        }
+        match &self.matcher {
+            BranchMatcher::Glob(matcher) => matcher.is_match(branch),
+            BranchMatcher::Regex(regex) => regex.is_match(branch),
+        }
+    }
+}
+
+impl TryFrom<String> for BranchPattern {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        let matcher = match source
+            .strip_prefix('/')
+            .and_then(|pattern| pattern.strip_suffix('/'))
+        {
+            Some(regex) => {
+                BranchMatcher::Regex(Regex::new(&format!("^(?:{regex})$")).map_err(|err| {
+                    match err.syntax_error() {
+                        // The last line of the message, without the pattern.
+                        Some(err) => {
+                            let message = err.to_string();
+                            let reason = message.lines().last().unwrap_or_default();
+                            format!(
+                                "invalid regex `{regex}`: {}",
+                                reason.trim_start_matches("error: ")
+                            )
+                        }
+                        None => err.to_string(),
+                    }
+                })?)
+            }
+            None => BranchMatcher::Glob(glob_matcher(&source).map_err(|err| err.to_string())?),
+        };
+        Ok(BranchPattern { source, matcher })
+    }
+}
+
+impl From<BranchPattern> for String {
+    fn from(pattern: BranchPattern) -> Self {
+        pattern.source
+    }
+}
+
+impl fmt::Display for BranchPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+pub fn glob_matcher(pattern: &str) -> Result<GlobMatcher> {
+    Ok(GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()?
+        .compile_matcher())
+}
+
+/// Same rules as `includeIf "gitdir:..."`: `~/` is expanded to the home
+/// directory, relative patterns match anywhere and a trailing `/` matches
+/// everything below.
+fn gitdir_pattern(pattern: &str) -> String {
+    let mut pattern = match (pattern.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home_dir)) => format!("{}/{rest}", home_dir.display()),
+        _ => pattern.to_string(),
+    };
+    if !Path::new(&pattern).is_absolute() && !pattern.starts_with("**/") {
+        pattern.insert_str(0, "**/");
+    }
+    if pattern.ends_with('/') {
+        pattern.push_str("**");
+    }
+    pattern
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(source: &str) -> BranchPattern {
+        BranchPattern::try_from(source.to_string()).unwrap()
+    }
+
+    #[test]
+    fn glob_matches_within_one_level() {
+        let pattern = pattern("release/*");
+
+        assert!(pattern.is_match("release/1.0"));
+        assert!(!pattern.is_match("release/1.0/fix"));
+        assert!(!pattern.is_match("hotfix/release/1.0"));
+    }
+
+    #[test]
+    fn regex_matches_the_whole_name() {
+        let pattern = pattern("/hotfix-[0-9]+/");
+
+        assert!(pattern.is_match("hotfix-12"));
+        assert!(!pattern.is_match("hotfix-12a"));
+        assert!(!pattern.is_match("my-hotfix-12"));
+    }
+
+    #[test]
+    fn alternatives_of_a_regex_are_anchored() {
+        let pattern = pattern("/main|develop/");
+
+        assert!(pattern.is_match("develop"));
+        assert!(!pattern.is_match("main-old"));
+    }
+
+    #[test]
+    fn a_single_slash_is_a_glob() {
+        assert!(pattern("/").is_match("/"));
+    }
+
+    #[test]
+    fn detached_head_never_matches() {
+        assert!(!pattern("*").is_match(""));
+        assert!(!pattern("/.*/").is_match(""));
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        let err = BranchPattern::try_from("/(/".to_string()).unwrap_err();
+        assert!(err.starts_with("invalid regex `(`: "), "{err}");
+
+        assert!(BranchPattern::try_from("[".to_string()).is_err());
+    }
+
+    #[test]
+    fn relative_gitdir_patterns_match_anywhere() {
+        assert_eq!(gitdir_pattern("work/"), "**/work/**");
+        assert_eq!(gitdir_pattern("/srv/*/.git"), "/srv/*/.git");
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::{anyhow, bail, Result};
+use std::{fs, path::Path};
+use toml::{Table, Value};
+use toml_edit::{DocumentMut, Item};
+
+use crate::git::backend::GitBackend;
+
+use super::{
+    is_map_entry, load_config_layers, lookup, merge_tables, parse_value, resolve_config,
+    resolve_custom_segments, shell_section_path, types::Config, ConfigOptions,
+};
+
+/// The effective value of a dotted `key` (e.g. `stash_suffix.color`).
+pub fn get_config_value(
+    options: ConfigOptions,
+    backend: Option<&mut dyn GitBackend>,
+    key: &str,
+) -> Result<String> {
+    let mut config = load_config_layers(options, backend)?.config;
+    resolve_custom_segments(&mut config)?;
+    let path = config_path(key);
+
+    match lookup(&config, &path) {
+        Some(Value::String(value)) => Ok(value.clone()),
+        Some(Value::Table(table)) => Ok(toml::to_string(table)?.trim_end().into()),
+        Some(value) => Ok(value.to_string()),
+        None => bail!("Unknown config key `{key}`"),
+    }
+}
+
+/// Sets a dotted `key` in `config_file`, keeping the comments and formatting
+/// of the rest of the file. `value` is read as TOML value if possible and as
+/// plain string otherwise.
+pub fn set_config_value(config_file: &Path, key: &str, value: &str) -> Result<()> {
+    let defaults = Table::try_from(Config::default())?;
+    let path = config_path(key);
+    let Some((key_name, sections)) = path.split_last() else {
+        bail!("Empty config key");
+    };
+    match lookup(&defaults, shell_section_path(&path)) {
+        Some(Value::Table(_)) => bail!("`{key}` is a table, set its values instead"),
+        Some(_) => (),
+        None if is_map_entry(&path) => (),
+        None => bail!("Unknown config key `{key}`"),
+    }
+
+    let content = if config_file.exists() {
+        fs::read_to_string(config_file)?
+    } else {
+        String::new()
+    };
+    let mut document: DocumentMut = content.parse()?;
+    let new_value: toml_edit::Value = parse_value(value).to_string().parse()?;
+    // In a file with only comments (e.g. from `init-config`) keep them in
+    // front of the new entry.
+    let leading_comments = document.as_table().is_empty().then(|| {
+        let comments = document.trailing().as_str().unwrap_or_default().to_string();
+        document.set_trailing("");
+        comments
+    });
+
+    let mut item = document.as_item_mut();
+    for (index, section) in sections.iter().enumerate() {
+        let table = item
+            .as_table_like_mut()
+            .ok_or_else(|| anyhow!("`{section}` is not a table in {}", config_file.display()))?;
+        if table.get(section).is_none() {
+            // Tables holding only sub-tables get no header of their own.
+            let mut new_table = toml_edit::Table::new();
+            new_table.set_implicit(index + 1 < sections.len());
+            table.insert(section, Item::Table(new_table));
+        }
+        item = table
+            .get_mut(section)
+            .ok_or_else(|| anyhow!("`{section}` is not a table"))?;
+    }
+    let table = item
+        .as_table_like_mut()
+        .ok_or_else(|| anyhow!("`{key}` is not in a table in {}", config_file.display()))?;
+    match table.get_mut(key_name) {
+        Some(Item::Value(existing)) => {
+            let decor = existing.decor().clone();
+            *existing = new_value;
+            *existing.decor_mut() = decor;
+        }
+        _ => {
+            table.insert(key_name, Item::Value(new_value));
+        }
+    }
+
+    if let Some(comments) = leading_comments {
+        let root = document.as_table_mut();
+        match sections.first() {
+            Some(_) => {
+                // The first table with a header, the new ones are implicit.
+                let mut current = Some(root);
+                for section in sections {
+                    let Some(table) = current
+                        .and_then(|table| table.get_mut(section))
+                        .and_then(Item::as_table_mut)
+                    else {
+                        break;
+                    };
+                    if !table.is_implicit() {
+                        table.decor_mut().set_prefix(comments);
+                        break;
+                    }
+                    current = Some(table);
+                }
+            }
+            None => {
+                if let Some(mut key) = root.key_mut(key_name) {
+                    key.leaf_decor_mut().set_prefix(comments);
+                }
+            }
+        }
+    }
+
+    let mut config = defaults;
+    merge_tables(
+        &mut config,
+        document.to_string().parse()?,
+        "",
+        &mut |_, _| (),
+    );
+    resolve_config(&mut config)?;
+    if let Err(err) = config.try_into::<Config>() {
+        bail!("Invalid value for `{key}`: {}", err.message());
+    }
+
+    if let Some(config_dir) = config_file.parent() {
+        fs::create_dir_all(config_dir)?;
+    }
+    fs::write(config_file, document.to_string())?;
+
+    Ok(())
+}
+
+fn config_path(key: &str) -> Vec<String> {
+    key.split('.').map(String::from).collect()
+}
@@ -0,0 +1,190 @@
+use anyhow::{bail, Result};
+use serde_json::Value as JsonValue;
+use std::{
+    collections::HashSet,
+    fmt::{self, Write},
+    fs,
+    path::Path,
+};
+use toml::{Table, Value};
+
+use super::{schema::config_schema, types::Config};
+
+const HEADER: &str = "git-radar-rs configuration, generated by `git-radar-rs init-config`.
+
+All values are the defaults, uncomment and change them as needed.";
+
+/// Writes the commented default configuration to `config_file`.
+pub fn init_config(config_file: &Path, force: bool) -> Result<()> {
+    if config_file.exists() && !force {
+        bail!(
+            "{} already exists, use --force to overwrite it",
+            config_file.display()
+        );
+    }
+    if let Some(config_dir) = config_file.parent() {
+        fs::create_dir_all(config_dir)?;
+    }
+    fs::write(config_file, default_config_template()?)?;
+
+    Ok(())
+}
+
+/// The default configuration with every value commented out and described by
+/// the documentation of the corresponding `Config` field.
+pub fn default_config_template() -> Result<String> {
+    let schema = config_schema();
+    let defaults = Table::try_from(Config::default())?;
+    let mut template = Template {
+        root: schema.as_value(),
+        documented_types: HashSet::new(),
+        output: String::new(),
+    };
+
+    template.comment(HEADER)?;
+    template.output.push('\n');
+    if let Some(description) = description(template.root) {
+        template.comment(description)?;
+        template.output.push('\n');
+    }
+    template.entries(template.root, &defaults, "", true)?;
+
+    Ok(template.output)
+}
+
+struct Template<'a> {
+    root: &'a JsonValue,
+    /// Types whose fields were already described, e.g. the first `ColoredTag`.
+    documented_types: HashSet<&'a str>,
+    output: String,
+}
+
+impl<'a> Template<'a> {
+    fn comment(&mut self, text: &str) -> fmt::Result {
+        for line in text.lines() {
+            if line.is_empty() {
+                writeln!(self.output, "#")?;
+            } else {
+                writeln!(self.output, "# {line}")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// All plain values first (as required by TOML), then the tables.
+    fn entries(
+        &mut self,
+        schema: &'a JsonValue,
+        values: &Table,
+        prefix: &str,
+        describe: bool,
+    ) -> fmt::Result {
+        let Some(properties) = schema.get("properties").and_then(JsonValue::as_object) else {
+            return Ok(());
+        };
+
+        for (key, property) in properties {
+            let Some(value) = values.get(key).filter(|value| !value.is_table()) else {
+                continue;
+            };
+            if describe {
+                if let Some(description) = description(property) {
+                    self.comment(description)?;
+                }
+                self.allowed_values(self.resolve(property))?;
+            }
+            writeln!(self.output, "# {key} = {value}")?;
+            if prefix.is_empty() {
+                self.output.push('\n');
+            }
+        }
+
+        for (key, property) in properties {
+            let Some(Value::Table(table)) = values.get(key) else {
+                continue;
+            };
+            let describe_fields = match reference(property) {
+                Some(type_name) => self.documented_types.insert(type_name),
+                None => true,
+            };
+            if let Some(description) = description(property) {
+                self.comment(description)?;
+            }
+            writeln!(self.output, "# [{prefix}{key}]")?;
+            self.entries(
+                self.resolve(property),
+                table,
+                &format!("{prefix}{key}."),
+                describe_fields,
+            )?;
+            self.output.push('\n');
+        }
+
+        Ok(())
+    }
+
+    fn allowed_values(&mut self, schema: &JsonValue) -> fmt::Result {
+        if let Some(values) = schema.get("enum").and_then(JsonValue::as_array) {
+            let values: Vec<String> = values.iter().map(ToString::to_string).collect();
+            self.comment(&format!("One of: {}", values.join(", ")))?;
+        }
+        if let Some(variants) = schema.get("anyOf").and_then(JsonValue::as_array) {
+            // Untagged values like colors accept more than the names of one
+            // variant, the description lists their forms instead.
+            let variants: Vec<_> = variants
+                .iter()
+                .map(|variant| self.resolve(variant))
+                .collect();
+            if variants.iter().all(|variant| variant.get("enum").is_some()) {
+                for variant in variants {
+                    self.allowed_values(variant)?;
+                }
+            }
+        }
+        if let Some(variants) = schema.get("oneOf").and_then(JsonValue::as_array) {
+            for variant in variants {
+                if let Some(value) = variant.get("const") {
+                    let description = description(variant).unwrap_or_default();
+                    self.comment(&format!("  {value}: {description}"))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve(&self, schema: &'a JsonValue) -> &'a JsonValue {
+        match reference(schema) {
+            Some(type_name) => self
+                .root
+                .get("$defs")
+                .and_then(|defs| defs.get(type_name))
+                .unwrap_or(schema),
+            None => schema,
+        }
+    }
+}
+
+fn description(schema: &JsonValue) -> Option<&str> {
+    schema.get("description").and_then(JsonValue::as_str)
+}
+
+fn reference(schema: &JsonValue) -> Option<&str> {
+    schema
+        .get("$ref")
+        .and_then(JsonValue::as_str)
+        .and_then(|reference| reference.strip_prefix("#/$defs/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_only_complete_allowed_values() {
+        let template = default_config_template().unwrap();
+
+        assert!(template.contains(r#"# One of: "dull", "vivid""#));
+        assert!(!template.contains(r#"# One of: "black""#));
+        assert!(template.contains("256-color index, `#rrggbb` or the name of a `palette` color"));
+    }
+}
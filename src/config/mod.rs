@@ -1,15 +1,682 @@
-use anyhow::Result;
-use std::fs;
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::{self, Write},
+    fs,
+    path::{Path, PathBuf},
+};
+use toml::{Table, Value};
 
+use crate::{
+    git::backend::GitBackend,
+    terminal::{template::Template, types::Shell},
+};
+
+use self::{
+    conditional::RepoContext,
+    palette::resolve_palette,
+    theme::Theme,
+    types::{ConditionalConfig, Config, CustomSegment},
+};
+
+pub mod check;
+pub mod conditional;
+pub mod edit;
+pub mod init;
+pub mod palette;
+pub mod schema;
+pub mod theme;
 pub mod types;
 
-pub fn get_app_config() -> Result<types::Config> {
-    if let Some(config_dir) = dirs::config_dir() {
-        let config_file = config_dir.join("git-radar-rs").join("config.toml");
+const GIT_CONFIG_SECTION: &str = "radar";
+const ENV_PREFIX: &str = "GIT_RADAR_";
+/// Tables with arbitrary keys, e.g. `palette.ok` or `custom.jira`.
+const MAP_KEYS: &[&str] = &["palette", "custom"];
+/// Keys only read from the global configuration file and the environment:
+/// custom segments run their commands in every repository and the committed
+/// `.git-radar.toml` is only read if `trust_repo_config` is set.
+const TRUSTED_KEYS: &[&str] = &["custom", "trust_repo_config"];
+/// Deprecated top-level keys and their replacements.
+const DEPRECATED_KEYS: &[(&str, &str)] = &[("exclude_merge_commits", "merge_commits")];
+
+/// Selects the configuration to load, set by command line arguments.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ConfigOptions<'a> {
+    /// Read this file instead of the global `config.toml`.
+    pub config_file: Option<&'a Path>,
+    /// Overrides the `theme` of the configuration.
+    pub theme: Option<Theme>,
+    /// Applies the `[shell.<shell>]` section.
+    pub shell: Option<Shell>,
+}
+
+/// Loads the configuration from (lowest precedence first) the global
+/// `config.toml` (or `config_file` if given), a committed `.git-radar.toml`
+/// in the working tree (if `trust_repo_config` is set), `.git/git-radar-rs.toml`, the `radar.*` keys of the
+/// git config, matching `[[conditional]]` sections, the section of the shell
+/// and `GIT_RADAR_*` environment variables. Later sources override earlier ones (and the
+/// theme and defaults) field by field. The `TRUSTED_KEYS` are ignored in the
+/// repository files, the git config and the conditional sections.
+pub fn get_app_config(
+    options: ConfigOptions,
+    backend: Option<&mut dyn GitBackend>,
+) -> Result<Config> {
+    let mut config = load_config_layers(options, backend)?.config;
+    resolve_config(&mut config)?;
+    Ok(config.try_into()?)
+}
+
+/// The defaults used in place of an invalid configuration, keeping the
+/// `config_error_indicator` of the config file if it is valid itself.
+pub fn fallback_config(options: ConfigOptions) -> Config {
+    let mut config = Config::default();
+    let config_file = options
+        .config_file
+        .map(Path::to_path_buf)
+        .or_else(global_config_file);
+    let Some(indicator) = config_file
+        .and_then(|config_file| fs::read_to_string(config_file).ok())
+        .and_then(|content| content.parse::<Table>().ok())
+        .and_then(|mut table| table.remove("config_error_indicator"))
+    else {
+        return config;
+    };
+
+    let mut layers = ConfigLayers::default();
+    if let Ok(defaults) = Table::try_from(&config) {
+        layers.merge(defaults, "default");
+    }
+    layers.merge(
+        Table::from_iter([("config_error_indicator".into(), indicator)]),
+        "config",
+    );
+    if let Some(Ok(indicator)) = layers
+        .config
+        .remove("config_error_indicator")
+        .map(Value::try_into)
+    {
+        config.config_error_indicator = indicator;
+    }
+    config
+}
+
+/// The effective configuration as dotted `key = value` lines, each annotated
+/// with the source the value came from.
+pub fn show_app_config(
+    options: ConfigOptions,
+    backend: Option<&mut dyn GitBackend>,
+) -> Result<String> {
+    let layers = load_config_layers(options, backend)?;
+    let mut config = layers.config.clone();
+    resolve_config(&mut config)?;
+    let _: Config = config.try_into()?;
+    let mut output = String::new();
+
+    layers.write_values(&mut output, "", &layers.config)?;
+
+    Ok(output)
+}
+
+#[derive(Default)]
+struct ConfigLayers {
+    config: Table,
+    sources: BTreeMap<String, String>,
+}
+
+impl ConfigLayers {
+    fn merge(&mut self, mut overrides: Table, source: &str) {
+        migrate_deprecated_keys(&mut overrides);
+        merge_tables(&mut self.config, overrides, "", &mut |key, value| {
+            record_sources(&mut self.sources, key, value, source)
+        });
+    }
+
+    /// Merges `overrides` without the `TRUSTED_KEYS`.
+    fn merge_untrusted(&mut self, mut overrides: Table, source: &str) {
+        remove_trusted_keys(&mut overrides);
+        self.merge(overrides, source);
+    }
+
+    fn merge_file(&mut self, config_file: &Path) -> Result<()> {
+        self.merge(read_file(config_file)?, &config_file.display().to_string());
+        Ok(())
+    }
+
+    fn merge_optional_file(&mut self, config_file: &Path) -> Result<()> {
         if config_file.exists() {
-            let content = fs::read_to_string(config_file)?;
-            return Ok(toml::from_str(&content)?);
+            self.merge_file(config_file)?;
+        }
+        Ok(())
+    }
+
+    fn merge_conditionals(&mut self, backend: &mut dyn GitBackend) -> Result<()> {
+        let conditionals: Vec<ConditionalConfig> = match self.config.get("conditional") {
+            Some(conditionals) => conditionals.clone().try_into()?,
+            None => return Ok(()),
+        };
+        if conditionals.is_empty() {
+            return Ok(());
+        }
+
+        let context = RepoContext::new(backend)?;
+        for (index, conditional) in conditionals.into_iter().enumerate() {
+            if conditional.matches(&context)? {
+                self.merge_untrusted(conditional.config, &format!("conditional #{}", index + 1));
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_shell(&mut self, shell: Shell) {
+        let Some(Value::Table(sections)) = self.config.get("shell") else {
+            return;
+        };
+        if let Some(Value::Table(overrides)) = sections.get(&shell.name()) {
+            self.merge(overrides.clone(), &format!("shell {}", shell.name()));
+        }
+    }
+
+    /// `GIT_RADAR_PARTS_SHOW_STASHES=false` overrides `parts.show_stashes`.
+    fn merge_env(&mut self) {
+        let vars: BTreeMap<String, String> = env::vars().collect();
+
+        for (name, value) in vars {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let Some(mut keys) = env_key_path(&self.config, &key.to_ascii_lowercase()) else {
+                continue;
+            };
+            let key = keys.pop().unwrap_or_default();
+            let mut overrides = Table::new();
+            if let Some(table) = nested_table(&mut overrides, keys) {
+                table.insert(key, parse_value(&value));
+            }
+            self.merge(overrides, &format!("env {name}"));
+        }
+    }
+
+    /// Applies the values of the theme selected by `theme` or the
+    /// configuration wherever no other source than the defaults set a value.
+    fn apply_theme(&mut self, theme: Option<Theme>) -> Result<()> {
+        let theme = match theme {
+            Some(theme) => {
+                self.merge(
+                    Table::from_iter([("theme".into(), Value::try_from(theme)?)]),
+                    "--theme",
+                );
+                theme
+            }
+            None => match self.config.get("theme") {
+                Some(theme) => theme.clone().try_into()?,
+                None => Theme::default(),
+            },
+        };
+
+        let mut overrides = theme.overrides()?;
+        remove_overridden(&mut overrides, "", &self.sources);
+        self.merge(overrides, &format!("theme {}", theme.name()));
+        Ok(())
+    }
+
+    fn write_values(&self, output: &mut String, prefix: &str, table: &Table) -> fmt::Result {
+        for (key, value) in table {
+            let key = format!("{prefix}{key}");
+            match value {
+                Value::Table(table) => self.write_values(output, &format!("{key}."), table)?,
+                value => {
+                    let source = self.sources.get(&key).map(String::as_str);
+                    writeln!(output, "{key} = {value} # {}", source.unwrap_or("default"))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn load_config_layers(
+    options: ConfigOptions,
+    backend: Option<&mut dyn GitBackend>,
+) -> Result<ConfigLayers> {
+    let mut layers = ConfigLayers::default();
+
+    layers.merge(Table::try_from(Config::default())?, "default");
+    match options.config_file {
+        Some(config_file) => layers.merge_file(config_file)?,
+        None => {
+            if let Some(config_file) = global_config_file() {
+                layers.merge_optional_file(&config_file)?;
+            }
+        }
+    }
+    if let Some(backend) = backend {
+        let trust_repo_config = trusts_repo_config(&layers.config);
+        for config_file in repo_config_files(backend, trust_repo_config)? {
+            if config_file.exists() {
+                layers
+                    .merge_untrusted(read_file(&config_file)?, &config_file.display().to_string());
+            }
+        }
+        layers.merge_untrusted(
+            git_config_table(backend.config_entries(GIT_CONFIG_SECTION)?),
+            "git config",
+        );
+        layers.merge_conditionals(backend)?;
+    }
+    if let Some(shell) = options.shell {
+        layers.merge_shell(shell);
+    }
+    layers.merge_env();
+    layers.apply_theme(options.theme)?;
+
+    Ok(layers)
+}
+
+fn read_file(config_file: &Path) -> Result<Table> {
+    let content = fs::read_to_string(config_file)
+        .map_err(|err| anyhow!("{}: {err}", config_file.display()))?;
+    Ok(content.parse()?)
+}
+
+pub fn global_config_file() -> Option<PathBuf> {
+    dirs::config_dir().map(|config_dir| config_dir.join("git-radar-rs").join("config.toml"))
+}
+
+/// Whether `trust_repo_config` is set by the environment or else by the
+/// global configuration `config`.
+fn trusts_repo_config(config: &Table) -> bool {
+    let env_value = env::var(format!("{ENV_PREFIX}TRUST_REPO_CONFIG"))
+        .ok()
+        .map(|value| parse_value(&value));
+    env_value
+        .as_ref()
+        .or(config.get("trust_repo_config"))
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+fn repo_config_files(
+    backend: &mut dyn GitBackend,
+    trust_repo_config: bool,
+) -> Result<Vec<PathBuf>> {
+    let mut config_files = Vec::new();
+    if trust_repo_config {
+        config_files.extend(work_tree_config_file(backend)?);
+    }
+    config_files.push(backend.git_dir()?.join("git-radar-rs.toml"));
+    Ok(config_files)
+}
+
+fn work_tree_config_file(backend: &mut dyn GitBackend) -> Result<Option<PathBuf>> {
+    Ok(backend
+        .work_dir()?
+        .map(|work_dir| work_dir.join(".git-radar.toml")))
+}
+
+/// Translates deprecated top-level keys (see `DEPRECATED_KEYS`) unless the
+/// replacement is set as well.
+fn migrate_deprecated_keys(table: &mut Table) {
+    if let Some(Value::Boolean(exclude_merges)) = table.remove("exclude_merge_commits") {
+        let merge_commits = if exclude_merges { "no-merges" } else { "all" };
+        table
+            .entry("merge_commits")
+            .or_insert_with(|| merge_commits.into());
+    }
+}
+
+/// Completes the values only valid in the configuration sources: partial
+/// `thresholds` are merged over their tag and palette names are replaced by
+/// their colors.
+fn resolve_config(config: &mut Table) -> Result<()> {
+    check_shell_sections(config)?;
+    resolve_thresholds(config);
+    resolve_custom_segments(config)?;
+    check_custom_segments(config)?;
+    resolve_palette(config)
+}
+
+/// Checks the configuration resulting from each shell section, they are only
+/// merged for their shell.
+fn check_shell_sections(config: &Table) -> Result<()> {
+    let Some(Value::Table(sections)) = config.get("shell") else {
+        return Ok(());
+    };
+    for (name, section) in sections {
+        let Value::Table(section) = section else {
+            continue;
+        };
+        let mut shell_config = config.clone();
+        shell_config.remove("shell");
+        merge_tables(&mut shell_config, section.clone(), "", &mut |_, _| ());
+        resolve_config(&mut shell_config)
+            .and_then(|()| {
+                shell_config
+                    .try_into::<Config>()
+                    .map_err(|err| anyhow!("{}", err.message()))
+            })
+            .with_context(|| format!("invalid `shell.{name}` section"))?;
+    }
+    Ok(())
+}
+
+/// Merges each custom segment over the defaults.
+fn resolve_custom_segments(config: &mut Table) -> Result<()> {
+    let defaults = Table::try_from(CustomSegment::default())?;
+    if let Some(Value::Table(segments)) = config.get_mut("custom") {
+        for (_, segment) in segments.iter_mut() {
+            if let Value::Table(overrides) = segment {
+                let mut merged = defaults.clone();
+                merge_tables(&mut merged, std::mem::take(overrides), "", &mut |_, _| ());
+                *overrides = merged;
+            }
         }
     }
-    Ok(Default::default())
+    Ok(())
+}
+
+/// Checks that the `format` uses only defined custom segments.
+fn check_custom_segments(config: &Table) -> Result<()> {
+    let segments = match config.get("custom") {
+        Some(Value::Table(segments)) => segments.clone(),
+        _ => Table::new(),
+    };
+
+    // An invalid format is reported when deserializing the configuration.
+    let Some(Ok(format)) = config
+        .get("format")
+        .and_then(Value::as_str)
+        .map(|format| Template::try_from(format.to_string()))
+    else {
+        return Ok(());
+    };
+    for name in format.custom_segments() {
+        if !segments.contains_key(name) {
+            return Err(anyhow!("undefined custom segment `{name}`"))
+                .context("invalid value for `format`");
+        }
+    }
+    Ok(())
+}
+
+fn resolve_thresholds(config: &mut Table) {
+    for (_, value) in config.iter_mut() {
+        let Value::Table(tag) = value else {
+            continue;
+        };
+        let Some(Value::Array(mut thresholds)) = tag.remove("thresholds") else {
+            continue;
+        };
+        for threshold in &mut thresholds {
+            if let Value::Table(overrides) = threshold {
+                let mut merged = tag.clone();
+                merge_tables(&mut merged, std::mem::take(overrides), "", &mut |_, _| ());
+                *overrides = merged;
+            }
+        }
+        tag.insert("thresholds".into(), Value::Array(thresholds));
+    }
+}
+
+fn merge_tables<F>(base: &mut Table, overrides: Table, prefix: &str, on_insert: &mut F)
+where
+    F: FnMut(&str, &Value),
+{
+    for (key, value) in overrides {
+        let path = format!("{prefix}{key}");
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(overrides)) => {
+                merge_tables(base, overrides, &format!("{path}."), on_insert)
+            }
+            (Some(Value::Array(_)), value) if !value.is_array() => {
+                let value = Value::Array(vec![value]);
+                on_insert(&path, &value);
+                base.insert(key, value);
+            }
+            (_, value) => {
+                on_insert(&path, &value);
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn record_sources(sources: &mut BTreeMap<String, String>, key: &str, value: &Value, source: &str) {
+    match value {
+        Value::Table(table) => {
+            for (name, value) in table {
+                record_sources(sources, &format!("{key}.{name}"), value, source);
+            }
+        }
+        _ => {
+            sources.insert(key.into(), source.into());
+        }
+    }
+}
+
+/// Removes the values of `table` already set by a source other than the
+/// defaults.
+fn remove_overridden(table: &mut Table, prefix: &str, sources: &BTreeMap<String, String>) {
+    table.retain(|key, value| {
+        let path = format!("{prefix}{key}");
+        match value {
+            Value::Table(table) => {
+                remove_overridden(table, &format!("{path}."), sources);
+                !table.is_empty()
+            }
+            _ => sources.get(&path).is_none_or(|source| source == "default"),
+        }
+    });
+}
+
+/// Resolves an underscore separated key (e.g. `parts_show_stashes`) against
+/// the known keys of `table`.
+fn env_key_path(table: &Table, key: &str) -> Option<Vec<String>> {
+    table.iter().find_map(|(name, value)| {
+        if key == name {
+            return Some(vec![name.clone()]);
+        }
+        let rest = key.strip_prefix(name.as_str())?.strip_prefix('_')?;
+        let Value::Table(table) = value else {
+            return None;
+        };
+        let mut path = env_key_path(table, rest)?;
+        path.insert(0, name.clone());
+        Some(path)
+    })
+}
+
+/// Removes the `TRUSTED_KEYS` from `table` and its shell sections.
+fn remove_trusted_keys(table: &mut Table) {
+    table.retain(|key, _| !TRUSTED_KEYS.contains(&key));
+    if let Some(Value::Table(sections)) = table.get_mut("shell") {
+        for (_, section) in sections.iter_mut() {
+            if let Value::Table(section) = section {
+                section.retain(|key, _| !TRUSTED_KEYS.contains(&key));
+            }
+        }
+    }
+}
+
+fn is_trusted_key(path: &[String]) -> bool {
+    matches!(shell_section_path(path), [key] if TRUSTED_KEYS.contains(&key.as_str()))
+}
+
+fn is_map_entry(path: &[String]) -> bool {
+    matches!(shell_section_path(path), [table, _, ..] if MAP_KEYS.contains(&table.as_str()))
+}
+
+/// The path of a key in a shell section relative to the section, e.g.
+/// `parts.show_stashes` for `shell.tmux.parts.show_stashes`.
+fn shell_section_path(path: &[String]) -> &[String] {
+    match path {
+        [shell, name, rest @ ..]
+            if shell == "shell" && !rest.is_empty() && Shell::from_str(name, false).is_ok() =>
+        {
+            rest
+        }
+        _ => path,
+    }
+}
+
+fn lookup<'a>(table: &'a Table, path: &[String]) -> Option<&'a Value> {
+    let (key, rest) = path.split_first()?;
+    match (table.get(key)?, rest) {
+        (value, []) => Some(value),
+        (Value::Table(table), rest) => lookup(table, rest),
+        _ => None,
+    }
+}
+
+fn nested_table(table: &mut Table, sections: Vec<String>) -> Option<&mut Table> {
+    let mut current = table;
+    for section in sections {
+        match current
+            .entry(section)
+            .or_insert_with(|| Value::Table(Table::new()))
+        {
+            Value::Table(table) => current = table,
+            _ => return None,
+        }
+    }
+    Some(current)
+}
+
+/// Maps git config entries like `radar.parts.show-stashes` to config keys
+/// (`parts.show_stashes`). A repeated key is collected into an array.
+fn git_config_table(entries: Vec<(String, String)>) -> Table {
+    let mut table = Table::new();
+
+    for (name, value) in entries {
+        let mut keys: Vec<String> = name
+            .split('.')
+            .skip(1)
+            .map(|key| key.replace('-', "_"))
+            .collect();
+        let Some(key) = keys.pop() else {
+            continue;
+        };
+        let Some(current) = nested_table(&mut table, keys) else {
+            continue;
+        };
+
+        let value = parse_value(&value);
+        let value = match current.remove(&key) {
+            None => value,
+            Some(Value::Array(mut values)) => {
+                values.push(value);
+                Value::Array(values)
+            }
+            Some(previous) => Value::Array(vec![previous, value]),
+        };
+        current.insert(key, value);
+    }
+
+    table
+}
+
+/// Reads a git config or environment value as TOML value if possible and as
+/// plain string otherwise.
+fn parse_value(value: &str) -> Value {
+    format!("value = {value}")
+        .parse::<Table>()
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(value.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(content: &str) -> Table {
+        content.parse().unwrap()
+    }
+
+    #[test]
+    fn merge_tables_overrides_field_by_field() {
+        let mut base = table("a = 1\n[parts]\nx = true\ny = true");
+        let mut inserted = Vec::new();
+
+        merge_tables(
+            &mut base,
+            table("b = 2\n[parts]\ny = false"),
+            "",
+            &mut |key, _| inserted.push(key.to_string()),
+        );
+
+        assert_eq!(base, table("a = 1\nb = 2\n[parts]\nx = true\ny = false"));
+        assert_eq!(inserted, ["b", "parts.y"]);
+    }
+
+    #[test]
+    fn merge_tables_wraps_single_values_of_arrays() {
+        let mut base = table("ignore = [\"a\", \"b\"]");
+
+        merge_tables(&mut base, table("ignore = \"c\""), "", &mut |_, _| ());
+
+        assert_eq!(base, table("ignore = [\"c\"]"));
+    }
+
+    #[test]
+    fn merge_tables_replaces_values_of_other_types() {
+        let mut base = table("[tag]\ncolor = \"red\"");
+
+        merge_tables(&mut base, table("tag = \"x\""), "", &mut |_, _| ());
+
+        assert_eq!(base, table("tag = \"x\""));
+    }
+
+    #[test]
+    fn env_key_path_resolves_underscores_against_known_keys() {
+        let config = Table::try_from(Config::default()).unwrap();
+        let path = |key| env_key_path(&config, key);
+
+        assert_eq!(
+            path("parts_show_stashes").unwrap(),
+            ["parts", "show_stashes"]
+        );
+        assert_eq!(path("stash_suffix_tag").unwrap(), ["stash_suffix", "tag"]);
+        assert_eq!(path("merge_commits").unwrap(), ["merge_commits"]);
+        assert_eq!(path("no_such_key"), None);
+    }
+
+    #[test]
+    fn git_config_entries_map_to_config_keys() {
+        let entries = vec![
+            ("radar.merge-commits".into(), "all".into()),
+            ("radar.parts.show-stashes".into(), "false".into()),
+            (
+                "radar.merge-branch-ignore-branches".into(),
+                "develop".into(),
+            ),
+            ("radar.merge-branch-ignore-branches".into(), "main".into()),
+        ];
+
+        assert_eq!(
+            git_config_table(entries),
+            table(
+                "merge_commits = \"all\"\n\
+                 merge_branch_ignore_branches = [\"develop\", \"main\"]\n\
+                 [parts]\nshow_stashes = false"
+            )
+        );
+    }
+
+    #[test]
+    fn untrusted_sources_drop_trusted_keys() {
+        let mut config = table(
+            "format = \"{branch}\"\ntrust_repo_config = true\n\
+             [custom.x]\ncommand = \"x\"\n\
+             [shell.tmux]\nformat = \"{branch}\"\n[shell.tmux.custom.y]\ncommand = \"y\"",
+        );
+
+        remove_trusted_keys(&mut config);
+
+        assert_eq!(
+            config,
+            table("format = \"{branch}\"\n[shell.tmux]\nformat = \"{branch}\"")
+        );
+    }
 }
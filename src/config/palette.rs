@@ -0,0 +1,109 @@
+use anyhow::{anyhow, Context, Result};
+use toml::{Table, Value};
+
+use crate::terminal::types::TermColor;
+
+/// Keys of color values which may name a `palette` color.
+const COLOR_KEYS: &[&str] = &["color", "background"];
+
+/// Replaces the names of `palette` colors in all color values of `config`.
+pub fn resolve_palette(config: &mut Table) -> Result<()> {
+    let palette = match config.get("palette") {
+        Some(Value::Table(palette)) => palette.clone(),
+        _ => Table::new(),
+    };
+    resolve_colors(config, &palette, "")
+}
+
+fn resolve_colors(table: &mut Table, palette: &Table, prefix: &str) -> Result<()> {
+    for (key, value) in table.iter_mut() {
+        if prefix.is_empty() && ["palette", "conditional", "shell"].contains(&key.as_str()) {
+            continue;
+        }
+        let path = format!("{prefix}{key}");
+        match value {
+            Value::Table(table) => resolve_colors(table, palette, &format!("{path}."))?,
+            Value::Array(values) => {
+                for (index, value) in values.iter_mut().enumerate() {
+                    if let Value::Table(table) = value {
+                        resolve_colors(table, palette, &format!("{path}.{index}."))?;
+                    }
+                }
+            }
+            Value::String(name) if COLOR_KEYS.contains(&key.as_str()) && !is_color(name) => {
+                *value = palette
+                    .get(name.as_str())
+                    .cloned()
+                    .ok_or_else(|| anyhow!("undefined palette color `{name}`"))
+                    .with_context(|| format!("invalid value for `{path}`"))?;
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Whether `name` is a color itself rather than the name of a palette color.
+fn is_color(name: &str) -> bool {
+    name.starts_with('#') || Value::String(name.into()).try_into::<TermColor>().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(content: &str) -> Result<Table> {
+        let mut config: Table = content.parse()?;
+        resolve_palette(&mut config)?;
+        Ok(config)
+    }
+
+    #[test]
+    fn replaces_palette_names() {
+        let config = resolve(
+            "[palette]\nok = \"green\"\n\
+             [stash_suffix]\ncolor = \"ok\"\nbackground = \"blue\"\n\
+             [[stash_suffix.thresholds]]\nmin = 3\ncolor = \"ok\"",
+        )
+        .unwrap();
+
+        assert_eq!(
+            config["stash_suffix"],
+            "color = \"green\"\nbackground = \"blue\"\n\
+             [[thresholds]]\nmin = 3\ncolor = \"green\""
+                .parse::<Table>()
+                .unwrap()
+                .into()
+        );
+    }
+
+    #[test]
+    fn keeps_colors() {
+        let config = resolve("[a]\ncolor = 208\n[b]\ncolor = \"#ff0000\"").unwrap();
+
+        assert_eq!(config["a"]["color"], Value::Integer(208));
+        assert_eq!(config["b"]["color"], Value::String("#ff0000".into()));
+    }
+
+    #[test]
+    fn reports_undefined_names_with_their_key() {
+        let err = resolve("[stash_suffix]\ncolor = \"nope\"").unwrap_err();
+
+        assert_eq!(err.to_string(), "invalid value for `stash_suffix.color`");
+        assert_eq!(
+            err.root_cause().to_string(),
+            "undefined palette color `nope`"
+        );
+    }
+
+    #[test]
+    fn reports_undefined_names_in_thresholds() {
+        let err = resolve("[stash_suffix]\n[[stash_suffix.thresholds]]\nbackground = \"nope\"")
+            .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "invalid value for `stash_suffix.thresholds.0.background`"
+        );
+    }
+}
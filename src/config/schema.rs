@@ -0,0 +1,29 @@
+use schemars::{generate::SchemaSettings, transform::RecursiveTransform, Schema};
+use serde_json::{json, Value};
+
+use super::types::Config;
+
+/// JSON Schema of the config file for editors and TOML language servers.
+///
+/// Every value is merged over the defaults, so no field is required.
+pub fn config_schema() -> Schema {
+    let mut schema = SchemaSettings::default()
+        .for_deserialize()
+        .with_transform(RecursiveTransform(remove_required))
+        .into_generator()
+        .into_root_schema_for::<Config>();
+
+    // Palette names are resolved before deserialization.
+    if let Some(Value::Array(colors)) = schema.pointer_mut("/$defs/TermColor/anyOf") {
+        colors.push(json!({
+            "description": "The name of a `palette` color.",
+            "type": "string"
+        }));
+    }
+
+    schema
+}
+
+fn remove_required(schema: &mut Schema) {
+    schema.remove("required");
+}
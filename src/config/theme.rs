@@ -0,0 +1,71 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use toml::Table;
+
+/// A preset for all values not set in the configuration.
+#[derive(
+    ValueEnum, Serialize, Deserialize, JsonSchema, Debug, Default, Clone, Copy, PartialEq, Eq,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    /// The git-radar look of the built-in defaults, the same as gitHUD's.
+    #[default]
+    #[serde(alias = "githud")]
+    #[value(alias = "githud")]
+    Classic,
+    /// ASCII characters only.
+    Minimal,
+    /// Icons of a Nerd Font.
+    NerdFont,
+}
+
+impl Theme {
+    pub fn name(self) -> String {
+        self.to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default()
+    }
+
+    pub fn description(self) -> String {
+        self.to_possible_value()
+            .and_then(|value| value.get_help().map(ToString::to_string))
+            .unwrap_or_default()
+    }
+
+    /// Other names selecting the theme, like `githud` for `classic`.
+    pub fn aliases(self) -> Vec<String> {
+        self.to_possible_value()
+            .map(|value| {
+                value
+                    .get_name_and_aliases()
+                    .skip(1)
+                    .map(ToString::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The values of the theme overriding the defaults.
+    pub fn overrides(self) -> Result<Table> {
+        let content = match self {
+            Theme::Classic => "",
+            Theme::Minimal => include_str!("themes/minimal.toml"),
+            Theme::NerdFont => include_str!("themes/nerd-font.toml"),
+        };
+        Ok(content.parse()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn githud_is_an_alias_of_classic() {
+        assert_eq!(Theme::Classic.aliases(), ["githud"]);
+        assert!(Theme::Minimal.aliases().is_empty());
+        assert_eq!(Theme::from_str("githud", false), Ok(Theme::Classic));
+    }
+}
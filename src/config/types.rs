@@ -1,15 +1,33 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-use crate::terminal::types::{BaseColor, Color, ColorIntensity, ColoredTag};
+use crate::{
+    git::types::{DivergenceOptions, GitQueries, MergeCommits},
+    terminal::{
+        template::{Placeholder, Style, Template},
+        types::{BaseColor, Color, ColorIntensity, ColoredTag, CountTag, Shell, TermColor},
+    },
+};
 
-#[derive(Serialize, Deserialize, Debug)]
+use super::{conditional::BranchPattern, theme::Theme};
+
+/// The parts of the prompt to show.
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 #[serde(default)]
 pub struct Parts {
+    /// Show the `repo_indicator` in front of the prompt.
     pub show_repo_indicator: bool,
+    /// Show the commits between the tracked branch and the remote default
+    /// branch (or the missing upstream).
     pub show_merge_branch_commits_diff: bool,
+    /// Show the local branch name (or tag / commit when detached).
     pub show_local_branch: bool,
+    /// Show the commits to push to and pull from the tracked branch.
     pub show_commits_to_origin: bool,
+    /// Show the staged, unstaged, untracked and conflicted changes.
     pub show_local_changes_state: bool,
+    /// Show the number of stashes.
     pub show_stashes: bool,
 }
 
@@ -26,188 +44,503 @@ impl Default for Parts {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// The spacing of the prompt, e.g. for compact or spaced-out layouts.
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[serde(default)]
+pub struct Spacing {
+    /// Written between non-empty segments for whitespace in the `format`.
+    pub separator: String,
+    /// Written between the parts of a segment, e.g. the merge branch indicator
+    /// and its counts.
+    pub inner: String,
+    /// Written between the staged, unstaged, untracked and conflicted changes.
+    pub change_groups: String,
+    /// Written between the count and the tag of changes and stashes.
+    pub count_tag: String,
+    /// Show the tag of changes and stashes `after` or `before` the count.
+    pub tag_position: TagPosition,
+}
+
+impl Default for Spacing {
+    fn default() -> Self {
+        Self {
+            separator: " ".into(),
+            inner: " ".into(),
+            change_groups: " ".into(),
+            count_tag: "".into(),
+            tag_position: TagPosition::After,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TagPosition {
+    Before,
+    After,
+}
+
+/// Config overrides applied only to repositories matching all of the given
+/// glob patterns, similar to git's `includeIf`.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default)]
+#[serde(default)]
+pub struct ConditionalConfig {
+    /// Glob matched against the git directory, `~/` is expanded and a
+    /// trailing `/` matches everything below.
+    pub gitdir: Option<String>,
+    /// Glob matched against the urls of all remotes.
+    pub remote_url: Option<String>,
+    /// Glob or `/regex/` matched against the local branch name.
+    #[schemars(with = "Option<String>")]
+    pub branch: Option<BranchPattern>,
+    /// The config values to apply.
+    #[schemars(with = "Config")]
+    pub config: toml::Table,
+}
+
+/// Hides or restyles placeholders of the `format` on matching branches, later
+/// rules take precedence.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default)]
+#[serde(default)]
+pub struct BranchRule {
+    /// Globs or `/regex/`, the rule applies if any of them matches the local
+    /// branch name.
+    #[schemars(with = "Vec<String>")]
+    pub branches: Vec<BranchPattern>,
+    /// Placeholders to hide, e.g. `["merge_branch", "stash"]`.
+    #[schemars(with = "Vec<String>")]
+    pub hide: Vec<Placeholder>,
+    /// Styles replacing those of the `format` by placeholder, e.g.
+    /// `branch = "red bold"`.
+    #[schemars(with = "BTreeMap<String, String>")]
+    pub style: BTreeMap<Placeholder, Style>,
+}
+
+impl BranchRule {
+    pub fn matches(&self, branch: &str) -> bool {
+        self.branches.iter().any(|pattern| pattern.is_match(branch))
+    }
+}
+
+/// A segment showing the first line of the output of a command.
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[serde(default)]
+pub struct CustomSegment {
+    /// Shell command run in the working directory, with the repository state
+    /// in `RADAR_*` environment variables (e.g. `RADAR_REPO_ROOT`,
+    /// `RADAR_BRANCH`). Nothing is shown if it fails.
+    pub command: String,
+    /// Kill the command after this many milliseconds and show nothing (0 to
+    /// wait until it finishes).
+    pub timeout_ms: u64,
+    /// Reuse the output in the same repository and branch for this many
+    /// seconds (0 to run the command for every prompt).
+    pub cache_seconds: u64,
+    /// The color of the output and a `tag` shown in front of it.
+    #[serde(flatten)]
+    pub tag: ColoredTag,
+}
+
+impl Default for CustomSegment {
+    fn default() -> Self {
+        Self {
+            command: String::new(),
+            timeout_ms: 500,
+            cache_seconds: 0,
+            tag: ColoredTag {
+                tag: "".into(),
+                color: Color::new(BaseColor::NoColor, ColorIntensity::Dull),
+            },
+        }
+    }
+}
+
+/// Configuration of git-radar-rs.
+///
+/// Tags consist of a `tag` string, its `color` and color `intensity`.
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 #[serde(default)]
 pub struct Config {
+    /// The theme providing all values not set in the configuration (see
+    /// `git-radar-rs themes`).
+    pub theme: Theme,
+    /// Named colors, usable in place of a color in every `color` and
+    /// `background` value.
+    pub palette: BTreeMap<String, TermColor>,
+
+    /// The parts of the prompt to show.
     pub parts: Parts,
+    /// The spacing between and within the parts of the prompt.
+    pub spacing: Spacing,
+    /// The layout of the prompt. `{repo_indicator}`, `{merge_branch}`,
+    /// `{branch}`, `{local_commits}`, `{changes}` and `{stash}` are replaced
+    /// by the parts of the prompt, `{local_branch}`, `{commit_tag}`,
+    /// `{commit_sha}`, `{remote_branch}`, `{merge_branch_name}`,
+    /// `{commits_to_push}`, `{commits_to_pull}`,
+    /// `{merge_branch_commits_to_push}`, `{merge_branch_commits_to_pull}`,
+    /// `{stash_count}`, `{index_add}`, `{index_mod}`, `{index_del}`,
+    /// `{local_add}`, `{local_mod}`, `{local_del}`, `{renamed}` and
+    /// `{conflicted}` by single values (nothing for zero counts).
+    ///
+    /// A style can be given after a colon, e.g. `{branch:208 bg=#303030
+    /// bold}`. Whitespace separates non-empty parts by a single
+    /// `spacing.separator`, `{{` and `}}` are literal braces.
+    ///
+    /// `{custom.<name>}` is replaced by the output of a `custom` segment.
+    ///
+    /// A group `{?...}` is omitted if any of its placeholders is empty, e.g.
+    /// `{?[{changes}]}` or `{?{commits_to_pull}⇅{commits_to_push}}`. Inside of
+    /// a group `}` always closes the group.
+    #[schemars(with = "String")]
+    pub format: Template,
+    /// Segments showing the output of commands, inserted with
+    /// `{custom.<name>}` in the `format`.
+    pub custom: BTreeMap<String, CustomSegment>,
+    /// Read the `.git-radar.toml` committed in the working tree of
+    /// repositories, only set in the global configuration file or the
+    /// environment.
+    pub trust_repo_config: bool,
+
+    /// Maximum time in milliseconds to spend on expensive git queries, values
+    /// not known by then are shown as `unknown_indicator` (0 to disable).
+    pub time_budget_ms: u64,
+    /// Persist the repository state in the cache directory and only recompute
+    /// the working tree status while the refs are unchanged.
+    pub state_cache: bool,
+    /// Shown in place of values that could not be computed in time.
+    pub unknown_indicator: ColoredTag,
+    /// Shown in front of the prompt if the configuration is invalid and the
+    /// defaults are used instead.
+    pub config_error_indicator: ColoredTag,
 
+    /// Stop counting commits beyond this limit and show `<limit>+` (0 to
+    /// count all commits).
+    pub commit_count_limit: usize,
+    /// How merge commits are counted in the commits to push and pull.
+    pub merge_commits: MergeCommits,
+
+    /// Shown in front of the prompt.
     pub repo_indicator: String,
 
+    /// Shown if the local branch does not track a remote branch.
     pub no_tracked_upstream_string: ColoredTag,
+    /// Shown after `no_tracked_upstream_string`.
     pub no_tracked_upstream_indicator: ColoredTag,
 
+    /// Shown in front of the commits between the tracked branch and the remote
+    /// default branch.
     pub merge_branch_commits_indicator: String,
-    pub merge_branch_commits_only_push: ColoredTag,
-    pub merge_branch_commits_only_pull: ColoredTag,
-    pub merge_branch_commits_both_pull_push: ColoredTag,
-    pub merge_branch_ignore_branches: Vec<String>,
+    /// Shown if the tracked branch is only ahead of the remote default branch.
+    pub merge_branch_commits_only_push: CountTag,
+    /// Shown if the tracked branch is only behind the remote default branch.
+    pub merge_branch_commits_only_pull: CountTag,
+    /// Shown between the commits behind and ahead of the remote default branch.
+    pub merge_branch_commits_both_pull_push: CountTag,
+    /// Local branches for which the merge branch and its commit counts are not
+    /// shown, as globs or `/regex/`.
+    #[schemars(with = "Vec<String>")]
+    pub merge_branch_ignore_branches: Vec<BranchPattern>,
 
+    /// Shown in front of the local branch name.
     pub local_branch_name_prefix: String,
+    /// Shown after the local branch name.
     pub local_branch_name_suffix: String,
+    /// Shown in front of the tag or commit of a detached `HEAD`.
     pub local_detached_prefix: String,
+    /// Color of the local branch name.
     pub local_branch_color: Color,
+    /// Color of the tag or commit of a detached `HEAD`.
     pub local_detached_color: Color,
 
-    pub local_commits_push_suffix: ColoredTag,
-    pub local_commits_pull_suffix: ColoredTag,
-    pub local_commits_push_pull_infix: ColoredTag,
+    /// Shown after the number of commits to push.
+    pub local_commits_push_suffix: CountTag,
+    /// Shown after the number of commits to pull.
+    pub local_commits_pull_suffix: CountTag,
+    /// Shown between the number of commits to pull and to push.
+    pub local_commits_push_pull_infix: CountTag,
+
+    /// Shown after the number of staged new files.
+    pub change_index_add_suffix: CountTag,
+    /// Shown after the number of staged modified files.
+    pub change_index_mod_suffix: CountTag,
+    /// Shown after the number of staged deleted files.
+    pub change_index_del_suffix: CountTag,
+    /// Shown after the number of untracked files.
+    pub change_local_add_suffix: CountTag,
+    /// Shown after the number of unstaged modified files.
+    pub change_local_mod_suffix: CountTag,
+    /// Shown after the number of unstaged deleted files.
+    pub change_local_del_suffix: CountTag,
+    /// Shown after the number of renamed files.
+    pub change_renamed_suffix: CountTag,
+    /// Shown after the number of conflicted files.
+    pub change_conflicted_suffix: CountTag,
 
-    pub change_index_add_suffix: ColoredTag,
-    pub change_index_mod_suffix: ColoredTag,
-    pub change_index_del_suffix: ColoredTag,
-    pub change_local_add_suffix: ColoredTag,
-    pub change_local_mod_suffix: ColoredTag,
-    pub change_local_del_suffix: ColoredTag,
-    pub change_renamed_suffix: ColoredTag,
-    pub change_conflicted_suffix: ColoredTag,
+    /// Shown after the number of stashes.
+    pub stash_suffix: CountTag,
 
-    pub stash_suffix: ColoredTag,
+    /// Config overrides for matching repositories.
+    pub conditional: Vec<ConditionalConfig>,
+    /// Placeholders of the `format` hidden or restyled on matching branches.
+    pub branch_rule: Vec<BranchRule>,
+    /// Config overrides for a single output target, e.g. `[shell.tmux]`.
+    #[schemars(with = "BTreeMap<Shell, Config>")]
+    pub shell: BTreeMap<Shell, toml::Table>,
 }
 
+impl Config {
+    /// The git queries needed to render the format on `local_branch` (empty
+    /// when HEAD is detached).
+    pub fn git_queries(&self, local_branch: &str) -> GitQueries {
+        GitQueries {
+            divergence: DivergenceOptions {
+                merge_commits: self.merge_commits,
+                limit: (self.commit_count_limit > 0).then_some(self.commit_count_limit),
+            },
+            ..self.format.git_queries(
+                |placeholder| self.is_shown(placeholder, local_branch),
+                local_branch.is_empty(),
+            )
+        }
+    }
+
+    /// Whether `placeholder` is shown on `local_branch`, considering the
+    /// `parts`, `merge_branch_ignore_branches` and the branch rules.
+    pub fn is_shown(&self, placeholder: &Placeholder, local_branch: &str) -> bool {
+        if placeholder.is_merge_branch()
+            && self
+                .merge_branch_ignore_branches
+                .iter()
+                .any(|pattern| pattern.is_match(local_branch))
+        {
+            return false;
+        }
+        placeholder.is_shown(&self.parts)
+            && !self
+                .matching_branch_rules(local_branch)
+                .any(|rule| rule.hide.contains(placeholder))
+    }
+
+    pub fn matching_branch_rules<'a>(
+        &'a self,
+        local_branch: &'a str,
+    ) -> impl Iterator<Item = &'a BranchRule> {
+        self.branch_rule
+            .iter()
+            .filter(move |rule| rule.matches(local_branch))
+    }
+}
+
+const DEFAULT_FORMAT: &str =
+    "{repo_indicator} {merge_branch} {branch} {local_commits} {changes} {stash}";
+
 impl Default for Config {
     fn default() -> Self {
         Self {
+            theme: Theme::default(),
+            palette: BTreeMap::new(),
+
             parts: Default::default(),
+            spacing: Default::default(),
+            format: DEFAULT_FORMAT
+                .to_string()
+                .try_into()
+                .expect("valid default format"),
+            custom: BTreeMap::new(),
+            trust_repo_config: false,
+
+            time_budget_ms: 0,
+            state_cache: false,
+            unknown_indicator: ColoredTag {
+                tag: "?".into(),
+                color: Color::new(BaseColor::Yellow, ColorIntensity::Vivid),
+            },
+            config_error_indicator: ColoredTag {
+                tag: "!".into(),
+                color: Color::new(BaseColor::Red, ColorIntensity::Vivid),
+            },
+
+            commit_count_limit: 0,
+            merge_commits: MergeCommits::NoMerges,
 
             repo_indicator: "ᚴ".into(),
 
             no_tracked_upstream_string: ColoredTag {
                 tag: "upstream".into(),
-                color: Color {
-                    color: BaseColor::Red,
-                    intensity: ColorIntensity::Vivid,
-                },
+                color: Color::new(BaseColor::Red, ColorIntensity::Vivid),
             },
             no_tracked_upstream_indicator: ColoredTag {
                 tag: "\u{26A1}".into(),
-                color: Color {
-                    color: BaseColor::Red,
-                    intensity: ColorIntensity::Vivid,
-                },
+                color: Color::new(BaseColor::Red, ColorIntensity::Vivid),
             },
 
             merge_branch_commits_indicator: "\u{1D62E}".into(),
             merge_branch_commits_only_push: ColoredTag {
                 tag: "\u{2190}".into(),
-                color: Color {
-                    color: BaseColor::Green,
-                    intensity: ColorIntensity::Vivid,
-                },
-            },
+                color: Color::new(BaseColor::Green, ColorIntensity::Vivid),
+            }
+            .into(),
             merge_branch_commits_only_pull: ColoredTag {
                 tag: "\u{2192}".into(),
-                color: Color {
-                    color: BaseColor::Green,
-                    intensity: ColorIntensity::Vivid,
-                },
-            },
+                color: Color::new(BaseColor::Green, ColorIntensity::Vivid),
+            }
+            .into(),
             merge_branch_commits_both_pull_push: ColoredTag {
                 tag: "\u{21C4}".into(),
-                color: Color {
-                    color: BaseColor::Green,
-                    intensity: ColorIntensity::Vivid,
-                },
-            },
-            merge_branch_ignore_branches: ["gh-pages".into()].into(),
+                color: Color::new(BaseColor::Green, ColorIntensity::Vivid),
+            }
+            .into(),
+            merge_branch_ignore_branches: vec!["gh-pages"
+                .to_string()
+                .try_into()
+                .expect("valid default pattern")],
 
             local_branch_name_prefix: "[".into(),
             local_branch_name_suffix: "]".into(),
             local_detached_prefix: "detached@".into(),
-            local_branch_color: Color {
-                color: BaseColor::NoColor,
-                intensity: ColorIntensity::Vivid,
-            },
-            local_detached_color: Color {
-                color: BaseColor::Yellow,
-                intensity: ColorIntensity::Vivid,
-            },
+            local_branch_color: Color::new(BaseColor::NoColor, ColorIntensity::Vivid),
+            local_detached_color: Color::new(BaseColor::Yellow, ColorIntensity::Vivid),
 
             local_commits_push_suffix: ColoredTag {
                 tag: "\u{2191}".into(),
-                color: Color {
-                    color: BaseColor::Green,
-                    intensity: ColorIntensity::Vivid,
-                },
-            },
+                color: Color::new(BaseColor::Green, ColorIntensity::Vivid),
+            }
+            .into(),
             local_commits_pull_suffix: ColoredTag {
                 tag: "\u{2193}".into(),
-                color: Color {
-                    color: BaseColor::Red,
-                    intensity: ColorIntensity::Vivid,
-                },
-            },
+                color: Color::new(BaseColor::Red, ColorIntensity::Vivid),
+            }
+            .into(),
             local_commits_push_pull_infix: ColoredTag {
                 tag: "\u{296F}".into(),
-                color: Color {
-                    color: BaseColor::Green,
-                    intensity: ColorIntensity::Vivid,
-                },
-            },
+                color: Color::new(BaseColor::Green, ColorIntensity::Vivid),
+            }
+            .into(),
 
             change_index_add_suffix: ColoredTag {
                 tag: "A".into(),
-                color: Color {
-                    color: BaseColor::Green,
-                    intensity: ColorIntensity::Vivid,
-                },
-            },
+                color: Color::new(BaseColor::Green, ColorIntensity::Vivid),
+            }
+            .into(),
             change_index_mod_suffix: ColoredTag {
                 tag: "M".into(),
-                color: Color {
-                    color: BaseColor::Green,
-                    intensity: ColorIntensity::Vivid,
-                },
-            },
+                color: Color::new(BaseColor::Green, ColorIntensity::Vivid),
+            }
+            .into(),
             change_index_del_suffix: ColoredTag {
                 tag: "D".into(),
-                color: Color {
-                    color: BaseColor::Green,
-                    intensity: ColorIntensity::Vivid,
-                },
-            },
+                color: Color::new(BaseColor::Green, ColorIntensity::Vivid),
+            }
+            .into(),
             change_local_add_suffix: ColoredTag {
                 tag: "A".into(),
-                color: Color {
-                    color: BaseColor::White,
-                    intensity: ColorIntensity::Vivid,
-                },
-            },
+                color: Color::new(BaseColor::White, ColorIntensity::Vivid),
+            }
+            .into(),
             change_local_mod_suffix: ColoredTag {
                 tag: "M".into(),
-                color: Color {
-                    color: BaseColor::Red,
-                    intensity: ColorIntensity::Vivid,
-                },
-            },
+                color: Color::new(BaseColor::Red, ColorIntensity::Vivid),
+            }
+            .into(),
             change_local_del_suffix: ColoredTag {
                 tag: "D".into(),
-                color: Color {
-                    color: BaseColor::Red,
-                    intensity: ColorIntensity::Vivid,
-                },
-            },
+                color: Color::new(BaseColor::Red, ColorIntensity::Vivid),
+            }
+            .into(),
             change_renamed_suffix: ColoredTag {
                 tag: "R".into(),
-                color: Color {
-                    color: BaseColor::Green,
-                    intensity: ColorIntensity::Vivid,
-                },
-            },
+                color: Color::new(BaseColor::Green, ColorIntensity::Vivid),
+            }
+            .into(),
             change_conflicted_suffix: ColoredTag {
                 tag: "C".into(),
-                color: Color {
-                    color: BaseColor::Green,
-                    intensity: ColorIntensity::Vivid,
-                },
-            },
+                color: Color::new(BaseColor::Green, ColorIntensity::Vivid),
+            }
+            .into(),
 
             stash_suffix: ColoredTag {
                 tag: "≡".into(),
-                color: Color {
-                    color: BaseColor::Green,
-                    intensity: ColorIntensity::Vivid,
-                },
-            },
+                color: Color::new(BaseColor::Green, ColorIntensity::Vivid),
+            }
+            .into(),
+
+            conditional: Vec::new(),
+            branch_rule: Vec::new(),
+            shell: BTreeMap::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: &str) -> Config {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn queries_the_tag_only_for_a_detached_branch() {
+        let config = config(r#"format = "{branch} {stash}""#);
+
+        assert!(!config.git_queries("main").commit_tag);
+        assert!(config.git_queries("").commit_tag);
+        assert!(config.git_queries("main").stash_count);
+    }
+
+    #[test]
+    fn skips_the_merge_branch_of_ignored_branches() {
+        let config = config(
+            r#"
+            format = "{merge_branch} {local_commits}"
+            merge_branch_ignore_branches = ["release/*"]
+            "#,
+        );
+
+        assert!(config.git_queries("main").merge_branch_commits);
+        assert_eq!(
+            config.git_queries("release/1.0"),
+            GitQueries {
+                commits_to_origin: true,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn skips_placeholders_hidden_by_branch_rules() {
+        let config = config(
+            r#"
+            format = "{merge_branch} {branch} {changes}"
+            commit_count_limit = 5
+
+            [[branch_rule]]
+            branches = ["main"]
+            hide = ["merge_branch", "changes"]
+            "#,
+        );
+
+        assert_eq!(
+            config.git_queries("feature"),
+            GitQueries {
+                merge_branch_commits: true,
+                local_repo_changes: true,
+                divergence: DivergenceOptions {
+                    limit: Some(5),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            config.git_queries("main"),
+            GitQueries {
+                divergence: DivergenceOptions {
+                    limit: Some(5),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    config::types::{Config, CustomSegment},
+    git::{
+        branch::build_fully_qualified_remote_branch_name,
+        cache::{stable_hash, write_atomically},
+        types::GitRepoState,
+    },
+};
+
+const ENV_PREFIX: &str = "RADAR_";
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CachedOutput {
+    created: SystemTime,
+    output: String,
+}
+
+/// Runs the custom segments used in the `format` in parallel and returns
+/// their outputs by name.
+pub fn run_custom_segments(
+    config: &Config,
+    repo_state: &GitRepoState,
+    work_dir: Option<&Path>,
+) -> BTreeMap<String, String> {
+    let names: BTreeSet<&str> = config.format.custom_segments().into_iter().collect();
+    if names.is_empty() {
+        return BTreeMap::new();
+    }
+    let env = segment_env(repo_state, work_dir);
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = names
+            .into_iter()
+            .filter_map(|name| Some((name, config.custom.get(name)?)))
+            .map(|(name, segment)| {
+                let env = &env;
+                (name, scope.spawn(move || segment_output(segment, env)))
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|(name, handle)| (name.to_string(), handle.join().unwrap_or_default()))
+            .collect()
+    })
+}
+
+fn segment_output(segment: &CustomSegment, env: &[(String, String)]) -> String {
+    let cache_file = (segment.cache_seconds > 0)
+        .then(|| cache_file(segment, env))
+        .flatten();
+    if let Some(output) = cache_file
+        .as_deref()
+        .and_then(|cache_file| load_cached_output(cache_file, segment.cache_seconds))
+    {
+        return output;
+    }
+
+    // Timed out commands are not cached, they are retried with the next prompt.
+    let Ok(Some(output)) = run_command(segment, env) else {
+        return String::new();
+    };
+    if let Some(cache_file) = cache_file {
+        store_cached_output(&cache_file, &output).ok();
+    }
+    output
+}
+
+/// The first line of the output, `None` if the command timed out.
+fn run_command(segment: &CustomSegment, env: &[(String, String)]) -> Result<Option<String>> {
+    let mut child = shell_command(&segment.command)
+        .envs(env.iter().map(|(name, value)| (name, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let mut stdout = child.stdout.take().expect("piped stdout");
+    let reader = thread::spawn(move || {
+        let mut output = Vec::new();
+        stdout.read_to_end(&mut output).map(|_| output)
+    });
+
+    let deadline = (segment.timeout_ms > 0)
+        .then(|| Instant::now() + Duration::from_millis(segment.timeout_ms));
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            child.kill().ok();
+            child.wait().ok();
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(5));
+    };
+
+    let output = reader.join().unwrap_or_else(|_| Ok(Vec::new()))?;
+    if !status.success() {
+        return Ok(Some(String::new()));
+    }
+    let output = String::from_utf8_lossy(&output);
+    Ok(Some(output.lines().next().unwrap_or("").trim().to_string()))
+}
+
+#[cfg(windows)]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.args(["/C", command]);
+    shell
+}
+
+#[cfg(not(windows))]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.args(["-c", command]);
+    shell
+}
+
+fn segment_env(repo_state: &GitRepoState, work_dir: Option<&Path>) -> Vec<(String, String)> {
+    let changes = &repo_state.git_local_repo_changes;
+    let remote_branch = if repo_state.remote_tracking_branch.is_empty() {
+        String::new()
+    } else {
+        build_fully_qualified_remote_branch_name(
+            &repo_state.remote,
+            &repo_state.remote_tracking_branch,
+        )
+    };
+
+    [
+        (
+            "REPO_ROOT",
+            work_dir
+                .map(|work_dir| work_dir.display().to_string())
+                .unwrap_or_default(),
+        ),
+        ("BRANCH", repo_state.local_branch.clone()),
+        ("COMMIT_SHA", repo_state.commit_short_sha.clone()),
+        ("COMMIT_TAG", repo_state.commit_tag.clone()),
+        ("REMOTE", repo_state.remote.clone()),
+        ("REMOTE_BRANCH", remote_branch),
+        ("MERGE_BRANCH", repo_state.merge_branch.clone()),
+        ("COMMITS_TO_PUSH", repo_state.commits_to_push.to_string()),
+        ("COMMITS_TO_PULL", repo_state.commits_to_pull.to_string()),
+        (
+            "MERGE_BRANCH_COMMITS_TO_PUSH",
+            repo_state.merge_branch_commits_to_push.to_string(),
+        ),
+        (
+            "MERGE_BRANCH_COMMITS_TO_PULL",
+            repo_state.merge_branch_commits_to_pull.to_string(),
+        ),
+        ("STASH_COUNT", repo_state.stash_count.to_string()),
+        ("INDEX_ADD", changes.index_add.to_string()),
+        ("INDEX_MOD", changes.index_mod.to_string()),
+        ("INDEX_DEL", changes.index_del.to_string()),
+        ("LOCAL_ADD", changes.local_add.to_string()),
+        ("LOCAL_MOD", changes.local_mod.to_string()),
+        ("LOCAL_DEL", changes.local_del.to_string()),
+        ("RENAMED", changes.renamed.to_string()),
+        ("CONFLICTED", changes.conflict.to_string()),
+    ]
+    .into_iter()
+    .map(|(name, value)| (format!("{ENV_PREFIX}{name}"), value))
+    .collect()
+}
+
+fn load_cached_output(cache_file: &Path, cache_seconds: u64) -> Option<String> {
+    let content = fs::read_to_string(cache_file).ok()?;
+    let cached: CachedOutput = toml::from_str(&content).ok()?;
+    let age = cached.created.elapsed().ok()?;
+
+    (age < Duration::from_secs(cache_seconds)).then_some(cached.output)
+}
+
+fn store_cached_output(cache_file: &Path, output: &str) -> Result<()> {
+    let cached = CachedOutput {
+        created: SystemTime::now(),
+        output: output.into(),
+    };
+
+    write_atomically(cache_file, &toml::to_string(&cached)?)
+}
+
+/// Outputs are cached per command, repository and branch.
+fn cache_file(segment: &CustomSegment, env: &[(String, String)]) -> Option<PathBuf> {
+    let keys = env
+        .iter()
+        .filter(|(name, _)| matches!(name.strip_prefix(ENV_PREFIX), Some("REPO_ROOT" | "BRANCH")))
+        .map(|(_, value)| value.as_bytes());
+    let hash = stable_hash([segment.command.as_bytes()].into_iter().chain(keys));
+
+    dirs::cache_dir().map(|cache_dir| {
+        cache_dir
+            .join("git-radar-rs")
+            .join("custom")
+            .join(format!("{hash:016x}.toml"))
+    })
+}
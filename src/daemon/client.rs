@@ -0,0 +1,84 @@
+use anyhow::{anyhow, Result};
+use std::{
+    env,
+    io::{Read, Write},
+    net::Shutdown,
+    os::unix::net::UnixStream,
+    path::Path,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::git::types::GitQueries;
+
+use super::{socket_path, DaemonAnswer, DaemonRequest, DaemonResponse};
+
+/// The longest wait for the daemon, the prompt is computed directly
+/// afterwards.
+const DAEMON_TIMEOUT: Duration = Duration::from_millis(500);
+
+pub fn query_daemon(queries: GitQueries, time_budget: Option<Duration>) -> DaemonAnswer {
+    let timeout = time_budget.map_or(DAEMON_TIMEOUT, |budget| budget.min(DAEMON_TIMEOUT));
+    match try_query_daemon(queries, timeout) {
+        Ok(answer) => answer,
+        Err(_) => DaemonAnswer::Unavailable,
+    }
+}
+
+fn try_query_daemon(queries: GitQueries, timeout: Duration) -> Result<DaemonAnswer> {
+    let Some(socket_path) = socket_path() else {
+        return Ok(DaemonAnswer::Unavailable);
+    };
+    if !socket_path.exists() {
+        return Ok(DaemonAnswer::Unavailable);
+    }
+    let request = DaemonRequest {
+        directory: env::current_dir()?,
+        queries,
+    };
+
+    request_repo_state(&socket_path, &request, timeout)
+}
+
+pub(super) fn request_repo_state(
+    socket_path: &Path,
+    request: &DaemonRequest,
+    timeout: Duration,
+) -> Result<DaemonAnswer> {
+    let deadline = Instant::now() + timeout;
+    let mut stream = connect(socket_path, timeout)?;
+    let remaining = deadline
+        .checked_duration_since(Instant::now())
+        .filter(|remaining| !remaining.is_zero())
+        .ok_or_else(|| anyhow!("Daemon timed out"))?;
+    stream.set_read_timeout(Some(remaining))?;
+    stream.set_write_timeout(Some(remaining))?;
+    stream.write_all(toml::to_string(request)?.as_bytes())?;
+    stream.shutdown(Shutdown::Write)?;
+
+    let mut content = String::new();
+    stream.read_to_string(&mut content)?;
+    let response: DaemonResponse = toml::from_str(&content)?;
+
+    match response {
+        DaemonResponse {
+            error: Some(error), ..
+        } => Err(anyhow!("{error}")),
+        DaemonResponse {
+            repo_state: Some(repo_state),
+            ..
+        } => Ok(DaemonAnswer::RepoState(Box::new(repo_state))),
+        _ => Ok(DaemonAnswer::NotInRepository),
+    }
+}
+
+/// Connects in a thread, a daemon not accepting connections would block the
+/// prompt otherwise.
+fn connect(socket_path: &Path, timeout: Duration) -> Result<UnixStream> {
+    let (sender, receiver) = mpsc::channel();
+    let socket_path = socket_path.to_owned();
+    thread::spawn(move || sender.send(UnixStream::connect(socket_path)));
+
+    Ok(receiver.recv_timeout(timeout)??)
+}
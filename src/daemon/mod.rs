@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::git::types::{GitQueries, GitRepoState};
+
+pub mod client;
+pub mod server;
+
+pub enum DaemonAnswer {
+    Unavailable,
+    NotInRepository,
+    RepoState(Box<GitRepoState>),
+}
+
+/// The queries of the client's config, the daemon caches a state per set of
+/// queries.
+#[derive(Serialize, Deserialize, Debug)]
+struct DaemonRequest {
+    directory: PathBuf,
+    queries: GitQueries,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+struct DaemonResponse {
+    repo_state: Option<GitRepoState>,
+    error: Option<String>,
+}
+
+pub fn socket_path() -> Option<PathBuf> {
+    dirs::runtime_dir()
+        .or_else(dirs::cache_dir)
+        .map(|dir| dir.join("git-radar-rs").join("daemon.sock"))
+}
@@ -0,0 +1,363 @@
+use anyhow::{anyhow, bail, Result};
+use notify::{ErrorKind, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    io::{Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+};
+
+use crate::git::{
+    backend::GitBackend,
+    get_git_repo_state, open_backend,
+    types::{GitQueries, GitRepoState},
+};
+
+use super::{socket_path, DaemonRequest, DaemonResponse};
+
+type OpenBackend = fn(&Path) -> Result<Option<Box<dyn GitBackend>>>;
+
+struct CachedRepository {
+    git_dir: PathBuf,
+    work_dir: Option<PathBuf>,
+    /// The directories of the working tree containing tracked files, watched
+    /// without their subdirectories so that ignored paths (like build
+    /// output) don't use up the watches.
+    watched_dirs: HashSet<PathBuf>,
+    /// Changes are noticed, false once the watch limit of the system is
+    /// reached. The state is not cached then.
+    watched: bool,
+    /// The states by the queries of the clients' configs.
+    repo_states: HashMap<GitQueries, GitRepoState>,
+}
+
+impl CachedRepository {
+    fn contains(&self, path: &Path) -> bool {
+        path.starts_with(&self.git_dir)
+            || self
+                .work_dir
+                .as_ref()
+                .is_some_and(|work_dir| path.starts_with(work_dir))
+    }
+}
+
+struct StateCache {
+    open_backend: OpenBackend,
+    watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    directories: HashMap<PathBuf, PathBuf>,
+    repositories: HashMap<PathBuf, CachedRepository>,
+}
+
+impl StateCache {
+    fn new(open_backend: OpenBackend) -> Result<StateCache> {
+        let (sender, events) = mpsc::channel();
+        let watcher = notify::recommended_watcher(move |event| {
+            sender.send(event).ok();
+        })?;
+
+        Ok(Self {
+            open_backend,
+            watcher,
+            events,
+            directories: HashMap::new(),
+            repositories: HashMap::new(),
+        })
+    }
+
+    fn invalidate(&mut self) {
+        for event in self.events.try_iter() {
+            let Ok(event) = event else {
+                // Events might have been lost, so nothing can be trusted anymore
+                for repository in self.repositories.values_mut() {
+                    repository.repo_states.clear();
+                }
+                continue;
+            };
+            if event.kind.is_access() {
+                continue;
+            }
+            for path in event.paths.iter().filter(|path| !is_lock_file(path)) {
+                for repository in self.repositories.values_mut() {
+                    if repository.contains(path) {
+                        repository.repo_states.clear();
+                    }
+                }
+            }
+        }
+    }
+
+    fn repo_state(
+        &mut self,
+        directory: &Path,
+        queries: GitQueries,
+    ) -> Result<Option<GitRepoState>> {
+        self.invalidate();
+
+        if let Some(git_dir) = self.directories.get(directory) {
+            if let Some(repo_state) = self
+                .repositories
+                .get(git_dir)
+                .and_then(|repository| repository.repo_states.get(&queries))
+            {
+                return Ok(Some(repo_state.clone()));
+            }
+        }
+
+        let Some(mut backend) = (self.open_backend)(directory)? else {
+            return Ok(None);
+        };
+        let git_dir = backend.git_dir()?;
+        let work_dir = backend.work_dir()?;
+
+        if !self.repositories.contains_key(&git_dir) {
+            let watched = watch(&mut self.watcher, &git_dir, RecursiveMode::Recursive)?;
+            self.repositories.insert(
+                git_dir.clone(),
+                CachedRepository {
+                    git_dir: git_dir.clone(),
+                    work_dir,
+                    watched_dirs: HashSet::new(),
+                    watched,
+                    repo_states: HashMap::new(),
+                },
+            );
+        }
+        self.directories.insert(directory.into(), git_dir.clone());
+        self.watch_tracked_dirs(&git_dir, backend.as_mut())?;
+
+        let repo_state = get_git_repo_state(backend, queries, None)?;
+
+        if let Some(repository) = self.repositories.get_mut(&git_dir) {
+            if repository.watched {
+                repository.repo_states.insert(queries, repo_state.clone());
+            }
+        }
+
+        Ok(Some(repo_state))
+    }
+
+    /// Updates the watched directories to the ones containing tracked files,
+    /// the index changes whenever files are added or removed.
+    fn watch_tracked_dirs(&mut self, git_dir: &Path, backend: &mut dyn GitBackend) -> Result<()> {
+        let Some(repository) = self.repositories.get_mut(git_dir) else {
+            return Ok(());
+        };
+        let Some(work_dir) = &repository.work_dir else {
+            return Ok(());
+        };
+        if !repository.watched {
+            return Ok(());
+        }
+
+        let mut tracked_dirs = HashSet::from([work_dir.clone()]);
+        for file in backend.tracked_files()? {
+            for dir in file.ancestors().skip(1) {
+                if !tracked_dirs.insert(work_dir.join(dir)) {
+                    break;
+                }
+            }
+        }
+
+        for dir in repository.watched_dirs.difference(&tracked_dirs) {
+            // Deleted directories are no longer watched anyway.
+            self.watcher.unwatch(dir).ok();
+        }
+        for dir in tracked_dirs.difference(&repository.watched_dirs) {
+            if !watch(&mut self.watcher, dir, RecursiveMode::NonRecursive)? {
+                repository.watched = false;
+                break;
+            }
+        }
+        repository.watched_dirs = tracked_dirs;
+
+        if !repository.watched {
+            eprintln!(
+                "Watch limit reached, not caching the state of {}",
+                work_dir.display()
+            );
+            for dir in repository.watched_dirs.drain() {
+                self.watcher.unwatch(&dir).ok();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Watches `path`, false if the watch limit of the system is reached.
+fn watch(watcher: &mut RecommendedWatcher, path: &Path, mode: RecursiveMode) -> Result<bool> {
+    match watcher.watch(path, mode) {
+        Ok(()) => Ok(true),
+        Err(err) if matches!(err.kind, ErrorKind::MaxFilesWatch) => Ok(false),
+        // Removed since the files were listed.
+        Err(err) if matches!(err.kind, ErrorKind::PathNotFound) => Ok(true),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub fn run_daemon() -> Result<()> {
+    let socket_path = socket_path().ok_or_else(|| anyhow!("No runtime directory"))?;
+
+    if let Some(socket_dir) = socket_path.parent() {
+        fs::create_dir_all(socket_dir)?;
+    }
+    if socket_path.exists() {
+        if UnixStream::connect(&socket_path).is_ok() {
+            bail!("Daemon already listening on {}", socket_path.display());
+        }
+        fs::remove_file(&socket_path)?;
+    }
+
+    // Prevent git from refreshing the index, which would invalidate the cache
+    // on every query
+    env::set_var("GIT_OPTIONAL_LOCKS", "0");
+
+    let listener = UnixListener::bind(&socket_path)?;
+
+    serve(listener, StateCache::new(open_directory)?)
+}
+
+fn open_directory(directory: &Path) -> Result<Option<Box<dyn GitBackend>>> {
+    env::set_current_dir(directory)?;
+    open_backend()
+}
+
+fn serve(listener: UnixListener, mut state_cache: StateCache) -> Result<()> {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(err) = handle_connection(stream, &mut state_cache) {
+                    eprintln!("Failed to answer request: {err}");
+                }
+            }
+            Err(err) => eprintln!("Failed to accept connection: {err}"),
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_connection(mut stream: UnixStream, state_cache: &mut StateCache) -> Result<()> {
+    let mut content = String::new();
+    stream.read_to_string(&mut content)?;
+    let request: DaemonRequest = toml::from_str(&content)?;
+
+    let response = match state_cache.repo_state(&request.directory, request.queries) {
+        Ok(repo_state) => DaemonResponse {
+            repo_state,
+            error: None,
+        },
+        Err(err) => DaemonResponse {
+            repo_state: None,
+            error: Some(err.to_string()),
+        },
+    };
+
+    stream.write_all(toml::to_string(&response)?.as_bytes())?;
+
+    Ok(())
+}
+
+fn is_lock_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "lock")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{process, thread, time::Duration};
+
+    use crate::{
+        config::types::Config,
+        daemon::{client::request_repo_state, DaemonAnswer},
+        git::{fake::FakeBackend, types::MergeCommits},
+        terminal::{prompt::Prompt, types::Shell},
+    };
+
+    use super::*;
+
+    fn test_dir() -> PathBuf {
+        env::temp_dir().join(format!("git-radar-rs-daemon-{}", process::id()))
+    }
+
+    fn open_fake(_directory: &Path) -> Result<Option<Box<dyn GitBackend>>> {
+        let mut backend = FakeBackend::new(GitRepoState {
+            local_branch: "feature".into(),
+            remote: "origin".into(),
+            remote_tracking_branch: "refs/heads/feature".into(),
+            commits_to_push: 2,
+            merges_to_push: 1,
+            ..Default::default()
+        });
+        backend.git_dir = test_dir();
+        backend.work_dir = None;
+
+        Ok(Some(Box::new(backend)))
+    }
+
+    /// Queries a daemon answering from the fake repository and renders the
+    /// prompt, like the client does.
+    fn render(socket_path: &Path, config: Config) -> String {
+        let request = DaemonRequest {
+            directory: test_dir(),
+            queries: config.git_queries("feature"),
+        };
+        let answer = request_repo_state(socket_path, &request, Duration::from_secs(5)).unwrap();
+        let DaemonAnswer::RepoState(repo_state) = answer else {
+            panic!("no repository state");
+        };
+
+        Prompt::new(Shell::None, config, *repo_state).to_string()
+    }
+
+    /// Starts a daemon answering from the fake repository.
+    fn start_daemon(name: &str) -> PathBuf {
+        let dir = test_dir();
+        fs::create_dir_all(&dir).unwrap();
+        let socket_path = dir.join(format!("{name}.sock"));
+        fs::remove_file(&socket_path).ok();
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let state_cache = StateCache::new(open_fake).unwrap();
+        thread::spawn(move || serve(listener, state_cache));
+
+        socket_path
+    }
+
+    fn config(merge_commits: MergeCommits, commit_count_limit: usize) -> Config {
+        Config {
+            format: "{commits_to_push}".to_string().try_into().unwrap(),
+            merge_commits,
+            commit_count_limit,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn answers_with_the_merge_commits_of_the_client() {
+        let socket_path = start_daemon("merge-commits");
+
+        assert_eq!(render(&socket_path, config(MergeCommits::Both, 0)), "2(1)");
+        assert_eq!(render(&socket_path, config(MergeCommits::NoMerges, 0)), "2");
+        assert_eq!(render(&socket_path, config(MergeCommits::All, 0)), "3");
+        assert_eq!(render(&socket_path, config(MergeCommits::Both, 0)), "2(1)");
+
+        fs::remove_file(&socket_path).ok();
+    }
+
+    #[test]
+    fn answers_with_the_commit_count_limit_of_the_client() {
+        let socket_path = start_daemon("commit-count-limit");
+
+        assert_eq!(render(&socket_path, config(MergeCommits::NoMerges, 0)), "2");
+        assert_eq!(
+            render(&socket_path, config(MergeCommits::NoMerges, 1)),
+            "1+"
+        );
+        assert_eq!(render(&socket_path, config(MergeCommits::Both, 1)), "1+(1)");
+        assert_eq!(render(&socket_path, config(MergeCommits::NoMerges, 0)), "2");
+
+        fs::remove_file(&socket_path).ok();
+    }
+}
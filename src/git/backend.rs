@@ -0,0 +1,46 @@
+use anyhow::Result;
+use std::path::PathBuf;
+
+use super::types::{Divergence, DivergenceOptions, GitLocalRepoChanges};
+
+/// Fine-grained queries against a git repository, implemented by the
+/// command-line, libgit and gitoxide backends (and an in-memory fake).
+pub trait GitBackend: Send {
+    fn git_dir(&mut self) -> Result<PathBuf>;
+
+    fn work_dir(&mut self) -> Result<Option<PathBuf>>;
+
+    fn local_branch(&mut self) -> Result<String>;
+
+    fn commit_short_sha(&mut self) -> Result<String>;
+
+    fn commit_tag(&mut self) -> Result<String>;
+
+    fn local_repo_changes(&mut self) -> Result<GitLocalRepoChanges>;
+
+    fn stash_count(&mut self) -> Result<usize>;
+
+    /// Paths of the files in the index, relative to the working tree.
+    fn tracked_files(&mut self) -> Result<Vec<PathBuf>>;
+
+    fn remote(&mut self, local_branch: &str) -> Result<String>;
+
+    fn remote_tracking_branch(&mut self, local_branch: &str) -> Result<String>;
+
+    fn remote_default_branch(&mut self, remote: &str) -> Result<String>;
+
+    /// All git config entries of `section` (e.g. `radar.parts.show-stashes`)
+    /// as `(name, value)` pairs, lowest precedence first.
+    fn config_entries(&mut self, section: &str) -> Result<Vec<(String, String)>>;
+
+    fn merge_base(&mut self, remote_default_branch: &str, local_branch: &str) -> Result<String>;
+
+    /// Commits reachable only from `from_commit` (to pull) and only from
+    /// `to_commit` (to push), counted in a single pass.
+    fn divergence(
+        &mut self,
+        from_commit: &str,
+        to_commit: &str,
+        options: &DivergenceOptions,
+    ) -> Result<Divergence>;
+}
@@ -0,0 +1,251 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, Instant, SystemTime},
+};
+
+use super::{
+    backend::GitBackend,
+    complete_git_repo_state, get_git_repo_state, initial_git_repo_state,
+    types::{GitQueries, GitRepoState},
+};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct FileStamp {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl FileStamp {
+    fn new(path: PathBuf) -> FileStamp {
+        match fs::metadata(&path) {
+            Ok(metadata) => FileStamp {
+                path,
+                modified: metadata.modified().ok(),
+                len: metadata.len(),
+            },
+            Err(_) => FileStamp {
+                path,
+                modified: None,
+                len: 0,
+            },
+        }
+    }
+
+    fn is_current(&self) -> bool {
+        *self == FileStamp::new(self.path.clone())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CachedRepoState {
+    git_dir: PathBuf,
+    queries: GitQueries,
+    stamps: Vec<FileStamp>,
+    repo_state: GitRepoState,
+}
+
+pub fn get_cached_git_repo_state(
+    mut backend: Box<dyn GitBackend>,
+    queries: GitQueries,
+    time_budget: Option<Duration>,
+) -> Result<GitRepoState> {
+    let deadline = time_budget.map(|time_budget| Instant::now() + time_budget);
+    let git_dir = backend.git_dir()?;
+    let Some(cache_file) = cache_file(&git_dir) else {
+        return get_git_repo_state(backend, queries, time_budget);
+    };
+
+    if let Some(mut repo_state) = load_cached_repo_state(&cache_file, &git_dir, queries) {
+        repo_state.unknown.local_repo_changes = queries.local_repo_changes;
+        return complete_git_repo_state(backend, repo_state, queries.divergence, deadline);
+    }
+
+    let repo_state = initial_git_repo_state(backend.as_mut(), queries)?;
+    // Taken before querying, so that refs changed meanwhile invalidate the
+    // stored state.
+    let stamps = relevant_files(&git_dir, &repo_state, queries)
+        .into_iter()
+        .map(FileStamp::new)
+        .collect();
+    let repo_state = complete_git_repo_state(backend, repo_state, queries.divergence, deadline)?;
+
+    let unknown = &repo_state.unknown;
+    if !(unknown.stash_count
+        || unknown.commit_tag
+        || unknown.commits_to_origin
+        || unknown.merge_branch_commits)
+    {
+        let cached = CachedRepoState {
+            git_dir,
+            queries,
+            stamps,
+            repo_state: repo_state.clone(),
+        };
+        store_cached_repo_state(&cache_file, &cached).ok();
+    }
+
+    Ok(repo_state)
+}
+
+fn load_cached_repo_state(
+    cache_file: &Path,
+    git_dir: &Path,
+    queries: GitQueries,
+) -> Option<GitRepoState> {
+    let content = fs::read_to_string(cache_file).ok()?;
+    let cached: CachedRepoState = toml::from_str(&content).ok()?;
+
+    if cached.git_dir == git_dir
+        && cached.queries == queries
+        && cached.stamps.iter().all(FileStamp::is_current)
+    {
+        Some(cached.repo_state)
+    } else {
+        None
+    }
+}
+
+fn store_cached_repo_state(cache_file: &Path, cached: &CachedRepoState) -> Result<()> {
+    write_atomically(cache_file, &toml::to_string(cached)?)
+}
+
+fn relevant_files(git_dir: &Path, repo_state: &GitRepoState, queries: GitQueries) -> Vec<PathBuf> {
+    let common_dir = common_dir(git_dir);
+    let mut files = vec![
+        git_dir.join("HEAD"),
+        git_dir.join("index"),
+        common_dir.join("config"),
+        common_dir.join("packed-refs"),
+        common_dir.join("logs").join("refs").join("stash"),
+    ];
+
+    if queries.commit_tag {
+        add_dirs(common_dir.join("refs").join("tags"), &mut files);
+    }
+
+    if !repo_state.local_branch.is_empty() {
+        files.push(
+            common_dir
+                .join("refs")
+                .join("heads")
+                .join(&repo_state.local_branch),
+        );
+    }
+    if !repo_state.remote.is_empty() {
+        let remote_refs = common_dir.join("refs").join("remotes");
+        let tracking_branch = repo_state
+            .remote_tracking_branch
+            .strip_prefix("refs/heads/")
+            .unwrap_or(&repo_state.remote_tracking_branch);
+
+        files.push(remote_refs.join(&repo_state.remote).join(tracking_branch));
+        files.push(remote_refs.join(&repo_state.remote).join("HEAD"));
+        if !repo_state.merge_branch.is_empty() {
+            files.push(remote_refs.join(&repo_state.merge_branch));
+        }
+    }
+
+    files
+}
+
+/// Adds `dir` and its subdirectories, whose modification times change when
+/// nested refs like `refs/tags/release/v2` are added or removed.
+fn add_dirs(dir: PathBuf, dirs: &mut Vec<PathBuf>) {
+    if let Ok(entries) = fs::read_dir(&dir) {
+        for entry in entries.flatten() {
+            if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                add_dirs(entry.path(), dirs);
+            }
+        }
+    }
+    dirs.push(dir);
+}
+
+fn common_dir(git_dir: &Path) -> PathBuf {
+    match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(common_dir) => git_dir.join(common_dir.trim_end()),
+        Err(_) => git_dir.into(),
+    }
+}
+
+fn cache_file(git_dir: &Path) -> Option<PathBuf> {
+    let hash = stable_hash([git_dir.as_os_str().as_encoded_bytes()]);
+
+    dirs::cache_dir().map(|cache_dir| {
+        cache_dir
+            .join("git-radar-rs")
+            .join("state")
+            .join(format!("{hash:016x}.toml"))
+    })
+}
+
+/// 64-bit FNV-1a hash of `parts`, unlike `DefaultHasher` it is the same for
+/// every build, so that cache file names stay valid across updates.
+pub fn stable_hash<'a>(parts: impl IntoIterator<Item = &'a [u8]>) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    let mut hash = OFFSET_BASIS;
+    for part in parts {
+        // The length separates the parts, ("ab", "c") differs from ("a", "bc").
+        for byte in (part.len() as u64).to_le_bytes().iter().chain(part) {
+            hash = (hash ^ u64::from(*byte)).wrapping_mul(PRIME);
+        }
+    }
+    hash
+}
+
+/// Writes `content` to a temporary file renamed to `path`, so that
+/// concurrent prompts never read a partially written file.
+pub fn write_atomically(path: &Path, content: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temp_file = path.with_extension(format!("{}.tmp", process::id()));
+    fs::write(&temp_file, content)?;
+    fs::rename(&temp_file, path).inspect_err(|_| {
+        fs::remove_file(&temp_file).ok();
+    })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn nested_tags_invalidate_the_stamps() {
+        let git_dir = env::temp_dir().join(format!("git-radar-rs-cache-{}", process::id()));
+        let release_dir = git_dir.join("refs").join("tags").join("release");
+        fs::create_dir_all(&release_dir).unwrap();
+        fs::write(release_dir.join("v1"), "1234567\n").unwrap();
+        // Any later change updates the modification time
+        fs::File::open(&release_dir)
+            .and_then(|dir| dir.set_modified(SystemTime::UNIX_EPOCH))
+            .unwrap();
+
+        let queries = GitQueries {
+            commit_tag: true,
+            ..Default::default()
+        };
+        let stamps: Vec<_> = relevant_files(&git_dir, &GitRepoState::default(), queries)
+            .into_iter()
+            .map(FileStamp::new)
+            .collect();
+        assert!(stamps.iter().any(|stamp| stamp.path == release_dir));
+        assert!(stamps.iter().all(FileStamp::is_current));
+
+        fs::write(release_dir.join("v2"), "1234567\n").unwrap();
+        assert!(!stamps.iter().all(FileStamp::is_current));
+
+        fs::remove_dir_all(&git_dir).ok();
+    }
+}
@@ -1,8 +1,34 @@
 use anyhow::Result;
-use std::str;
+use std::{path::PathBuf, str};
+
+use crate::git::types::{Divergence, DivergenceOptions, MergeCommits};
 
 use super::process::process_with_ignore_exit_code;
 
+pub fn git_cmd_git_dir() -> Result<PathBuf> {
+    Ok(str::from_utf8(&process_with_ignore_exit_code(
+        "git",
+        &["rev-parse", "--absolute-git-dir"],
+    )?)?
+    .trim_end()
+    .into())
+}
+
+pub fn git_cmd_work_dir() -> Result<Option<PathBuf>> {
+    let work_dir = str::from_utf8(&process_with_ignore_exit_code(
+        "git",
+        &["rev-parse", "--show-toplevel"],
+    )?)?
+    .trim_end()
+    .to_string();
+
+    if work_dir.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(work_dir.into()))
+    }
+}
+
 pub fn git_cmd_local_branch_name() -> Result<String> {
     Ok(str::from_utf8(&process_with_ignore_exit_code(
         "git",
@@ -12,10 +38,30 @@ pub fn git_cmd_local_branch_name() -> Result<String> {
     .into())
 }
 
-pub fn git_cmd_merge_base(local_branch_name: &str) -> Result<String> {
+pub fn git_cmd_config_entries(section: &str) -> Result<Vec<(String, String)>> {
+    let output = process_with_ignore_exit_code(
+        "git",
+        &[
+            "config",
+            "--null",
+            "--get-regexp",
+            &format!("^{section}\\."),
+        ],
+    )?;
+
+    Ok(str::from_utf8(&output)?
+        .split_terminator('\0')
+        .map(|entry| match entry.split_once('\n') {
+            Some((name, value)) => (name.into(), value.into()),
+            None => (entry.into(), "true".into()),
+        })
+        .collect())
+}
+
+pub fn git_cmd_merge_base(remote_default_branch: &str, local_branch_name: &str) -> Result<String> {
     Ok(str::from_utf8(&process_with_ignore_exit_code(
         "git",
-        &["merge-base", "origin/master", local_branch_name],
+        &["merge-base", remote_default_branch, local_branch_name],
     )?)?
     .trim_end()
     .into())
@@ -51,34 +97,69 @@ pub fn git_cmd_porcelain_status() -> Result<Vec<u8>> {
     process_with_ignore_exit_code("git", &["status", "--porcelain"])
 }
 
-pub fn git_cmd_rev_to_push(from_commit: &str, to_commit: &str) -> Result<usize> {
-    Ok(str::from_utf8(&process_with_ignore_exit_code(
-        "git",
-        &[
-            "rev-list",
-            "--no-merges",
-            "--right-only",
-            "--count",
-            &merge_base_diff_from_to(from_commit, to_commit),
-        ],
-    )?)?
-    .trim_end()
-    .parse()?)
+pub fn git_cmd_divergence(
+    from_commit: &str,
+    to_commit: &str,
+    options: &DivergenceOptions,
+) -> Result<Divergence> {
+    let (to_pull, to_push) = match options.merge_commits {
+        MergeCommits::All => git_cmd_left_right_count(from_commit, to_commit, None, options)?,
+        MergeCommits::NoMerges | MergeCommits::Both => {
+            git_cmd_left_right_count(from_commit, to_commit, Some("--no-merges"), options)?
+        }
+    };
+    let (merges_to_pull, merges_to_push) = match options.merge_commits {
+        MergeCommits::Both => {
+            git_cmd_left_right_count(from_commit, to_commit, Some("--merges"), options)?
+        }
+        MergeCommits::All | MergeCommits::NoMerges => (0, 0),
+    };
+
+    Ok(Divergence {
+        to_pull,
+        to_push,
+        merges_to_pull,
+        merges_to_push,
+    }
+    .capped(options))
 }
 
-pub fn git_cmd_rev_to_pull(from_commit: &str, to_commit: &str) -> Result<usize> {
-    Ok(str::from_utf8(&process_with_ignore_exit_code(
-        "git",
-        &[
-            "rev-list",
-            "--no-merges",
-            "--left-only",
-            "--count",
-            &merge_base_diff_from_to(from_commit, to_commit),
-        ],
-    )?)?
-    .trim_end()
-    .parse()?)
+/// The commits only in `from_commit` and only in `to_commit`. With a limit
+/// each side is counted separately, so that git stops after `limit + 1`
+/// commits.
+fn git_cmd_left_right_count(
+    from_commit: &str,
+    to_commit: &str,
+    filter: Option<&str>,
+    options: &DivergenceOptions,
+) -> Result<(usize, usize)> {
+    let Some(limit) = options.limit else {
+        let range = merge_base_diff_from_to(from_commit, to_commit);
+        let output = git_cmd_rev_list_count(&["--left-right"], filter, &range)?;
+        let mut counts = output.split_whitespace();
+        let left = counts.next().unwrap_or("0").parse()?;
+        let right = counts.next().unwrap_or("0").parse()?;
+        return Ok((left, right));
+    };
+
+    let max_count = format!("--max-count={}", limit + 1);
+    let count = |range: String| -> Result<usize> {
+        let output = git_cmd_rev_list_count(&[&max_count], filter, &range)?;
+        Ok(output.split_whitespace().next().unwrap_or("0").parse()?)
+    };
+    Ok((
+        count(format!("{to_commit}..{from_commit}"))?,
+        count(format!("{from_commit}..{to_commit}"))?,
+    ))
+}
+
+fn git_cmd_rev_list_count(options: &[&str], filter: Option<&str>, range: &str) -> Result<String> {
+    let mut args = vec!["rev-list", "--count"];
+    args.extend(options);
+    args.extend(filter);
+    args.push(range);
+
+    Ok(str::from_utf8(&process_with_ignore_exit_code("git", &args)?)?.into())
 }
 
 pub fn git_cmd_stash_count() -> Result<usize> {
@@ -88,6 +169,16 @@ pub fn git_cmd_stash_count() -> Result<usize> {
         .count())
 }
 
+pub fn git_cmd_tracked_files() -> Result<Vec<PathBuf>> {
+    Ok(str::from_utf8(&process_with_ignore_exit_code(
+        "git",
+        &["ls-files", "-z", "--full-name", ":/"],
+    )?)?
+    .split_terminator('\0')
+    .map(PathBuf::from)
+    .collect())
+}
+
 pub fn git_cmd_commit_short_sha() -> Result<String> {
     Ok(str::from_utf8(&process_with_ignore_exit_code(
         "git",
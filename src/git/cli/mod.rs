@@ -1,15 +1,20 @@
 use self::{
     command::{
-        git_cmd_commit_short_sha, git_cmd_commit_tag, git_cmd_local_branch_name,
-        git_cmd_merge_base, git_cmd_porcelain_status, git_cmd_remote_branch_name,
-        git_cmd_remote_name, git_cmd_rev_to_pull, git_cmd_rev_to_push, git_cmd_stash_count,
+        git_cmd_commit_short_sha, git_cmd_commit_tag, git_cmd_config_entries, git_cmd_divergence,
+        git_cmd_git_dir, git_cmd_local_branch_name, git_cmd_merge_base, git_cmd_porcelain_status,
+        git_cmd_remote_branch_name, git_cmd_remote_name, git_cmd_stash_count,
+        git_cmd_tracked_files, git_cmd_work_dir,
     },
     process::process_with_exit_code,
     status::git_parse_status,
 };
 use anyhow::Result;
+use std::path::PathBuf;
 
-use super::{branch::build_fully_qualified_remote_branch_name, types::GitRepoState};
+use super::{
+    backend::GitBackend,
+    types::{Divergence, DivergenceOptions, GitLocalRepoChanges},
+};
 
 pub mod command;
 pub mod process;
@@ -20,44 +25,78 @@ pub fn check_in_git_directory() -> Result<bool> {
     Ok(exit_code.success())
 }
 
-pub fn get_git_repo_state() -> Result<GitRepoState> {
-    let local_branch = git_cmd_local_branch_name()?;
-    let git_status = git_cmd_porcelain_status()?;
-    let git_local_repo_changes = git_parse_status(&git_status)?;
-    let remote = git_cmd_remote_name(&local_branch)?;
-    let stash_count = git_cmd_stash_count()?;
-    let commit_short_sha = git_cmd_commit_short_sha()?;
-    let commit_tag = git_cmd_commit_tag()?;
-
-    let mut repo_state = GitRepoState {
-        local_branch,
-        git_local_repo_changes,
-        remote,
-        stash_count,
-        commit_short_sha,
-        commit_tag,
-        ..Default::default()
-    };
-
-    if !repo_state.remote.is_empty() {
-        repo_state.remote_tracking_branch = git_cmd_remote_branch_name(&repo_state.local_branch)?;
-        let merge_base = git_cmd_merge_base(&repo_state.local_branch)?;
-
-        let full_remote_branch_name = build_fully_qualified_remote_branch_name(
-            &repo_state.remote,
-            &repo_state.remote_tracking_branch,
-        );
-
-        repo_state.commits_to_pull = git_cmd_rev_to_pull(&full_remote_branch_name, "HEAD")?;
-        repo_state.commits_to_push = git_cmd_rev_to_push(&full_remote_branch_name, "HEAD")?;
-
-        if !merge_base.is_empty() {
-            repo_state.merge_branch_commits_to_pull =
-                git_cmd_rev_to_pull("origin/master", &full_remote_branch_name)?;
-            repo_state.merge_branch_commits_to_push =
-                git_cmd_rev_to_push("origin/master", &full_remote_branch_name)?;
+pub struct CliBackend;
+
+impl CliBackend {
+    pub fn open() -> Result<Option<CliBackend>> {
+        if check_in_git_directory()? {
+            Ok(Some(CliBackend))
+        } else {
+            Ok(None)
         }
     }
+}
+
+impl GitBackend for CliBackend {
+    fn git_dir(&mut self) -> Result<PathBuf> {
+        git_cmd_git_dir()
+    }
+
+    fn work_dir(&mut self) -> Result<Option<PathBuf>> {
+        git_cmd_work_dir()
+    }
+
+    fn local_branch(&mut self) -> Result<String> {
+        git_cmd_local_branch_name()
+    }
+
+    fn commit_short_sha(&mut self) -> Result<String> {
+        git_cmd_commit_short_sha()
+    }
+
+    fn commit_tag(&mut self) -> Result<String> {
+        git_cmd_commit_tag()
+    }
+
+    fn local_repo_changes(&mut self) -> Result<GitLocalRepoChanges> {
+        git_parse_status(&git_cmd_porcelain_status()?)
+    }
 
-    Ok(repo_state)
+    fn stash_count(&mut self) -> Result<usize> {
+        git_cmd_stash_count()
+    }
+
+    fn tracked_files(&mut self) -> Result<Vec<PathBuf>> {
+        git_cmd_tracked_files()
+    }
+
+    fn remote(&mut self, local_branch: &str) -> Result<String> {
+        git_cmd_remote_name(local_branch)
+    }
+
+    fn remote_tracking_branch(&mut self, local_branch: &str) -> Result<String> {
+        git_cmd_remote_branch_name(local_branch)
+    }
+
+    /// The command-line backend always compares with `origin/master`.
+    fn remote_default_branch(&mut self, _remote: &str) -> Result<String> {
+        Ok("origin/master".into())
+    }
+
+    fn config_entries(&mut self, section: &str) -> Result<Vec<(String, String)>> {
+        git_cmd_config_entries(section)
+    }
+
+    fn merge_base(&mut self, remote_default_branch: &str, local_branch: &str) -> Result<String> {
+        git_cmd_merge_base(remote_default_branch, local_branch)
+    }
+
+    fn divergence(
+        &mut self,
+        from_commit: &str,
+        to_commit: &str,
+        options: &DivergenceOptions,
+    ) -> Result<Divergence> {
+        git_cmd_divergence(from_commit, to_commit, options)
+    }
 }
@@ -0,0 +1,416 @@
+use anyhow::Result;
+use std::{
+    collections::{BinaryHeap, HashMap},
+    hash::Hash,
+};
+
+use super::types::{Divergence, DivergenceOptions, DivergenceSide, MergeCommits};
+
+/// Number of hidden commits walked after the sides ran out, to catch up with
+/// commits whose dates are older than the ones of their parents (like
+/// `git rev-list`).
+const SLOP: usize = 5;
+
+/// The commit time and parents of a commit.
+pub struct CommitInfo<Id> {
+    pub time: i64,
+    pub parents: Vec<Id>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mark {
+    /// A merge base or one of its ancestors.
+    Hidden,
+    Side(DivergenceSide),
+}
+
+struct Node<Id> {
+    mark: Mark,
+    time: i64,
+    parents: Vec<Id>,
+    /// The parents were marked with the mark of this commit.
+    expanded: bool,
+    counted: bool,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct QueuedCommit<Id> {
+    time: i64,
+    /// On equal times hidden commits go first, so that they catch up with
+    /// the sides.
+    hidden: bool,
+    id: Id,
+}
+
+#[derive(Default, Clone, Copy)]
+struct SideCount {
+    commits: usize,
+    merges: usize,
+}
+
+/// Counts the commits reachable only from `from` (to pull) and only from
+/// `to` (to push) in a single walk, newest first. Every commit reachable from
+/// both is hidden by one of the `merge_bases`, every other commit belongs to
+/// the side of the child it was discovered from. A side is no longer walked
+/// once its count exceeds the limit of the options.
+pub fn walk_divergence<Id, F>(
+    from: Id,
+    to: Id,
+    merge_bases: &[Id],
+    options: &DivergenceOptions,
+    commit_info: F,
+) -> Result<Divergence>
+where
+    Id: Copy + Eq + Hash + Ord,
+    F: FnMut(Id) -> Result<CommitInfo<Id>>,
+{
+    let mut walk = Walk {
+        commit_info,
+        options,
+        nodes: HashMap::new(),
+        queue: BinaryHeap::new(),
+        interesting: 0,
+        paused: Vec::new(),
+        pull: SideCount::default(),
+        push: SideCount::default(),
+    };
+
+    for merge_base in merge_bases {
+        walk.discover(*merge_base, Mark::Hidden)?;
+    }
+    walk.discover(from, Mark::Side(DivergenceSide::Pull))?;
+    walk.discover(to, Mark::Side(DivergenceSide::Push))?;
+
+    walk.run()?;
+    Ok(walk.divergence().capped(options))
+}
+
+struct Walk<'a, Id, F> {
+    commit_info: F,
+    options: &'a DivergenceOptions,
+    nodes: HashMap<Id, Node<Id>>,
+    queue: BinaryHeap<QueuedCommit<Id>>,
+    /// Queued commits of the sides, the walk ends when only hidden ones are
+    /// left.
+    interesting: usize,
+    /// Commits of capped sides, walked again if hidden commits reduce the
+    /// count of their side below the limit.
+    paused: Vec<QueuedCommit<Id>>,
+    pull: SideCount,
+    push: SideCount,
+}
+
+impl<Id, F> Walk<'_, Id, F>
+where
+    Id: Copy + Eq + Hash + Ord,
+    F: FnMut(Id) -> Result<CommitInfo<Id>>,
+{
+    fn run(&mut self) -> Result<()> {
+        let mut slop = SLOP;
+        let mut last_side_time = i64::MAX;
+
+        while let Some(commit) = self.queue.pop() {
+            if commit.hidden {
+                self.hide_parents(commit.id)?;
+            } else {
+                self.interesting -= 1;
+                last_side_time = commit.time;
+                if let Mark::Side(side) = self.nodes[&commit.id].mark {
+                    self.walk_side(commit, side)?;
+                }
+                if self.is_capped(DivergenceSide::Pull) && self.is_capped(DivergenceSide::Push) {
+                    break;
+                }
+            }
+
+            if self.interesting > 0 {
+                slop = SLOP;
+            } else if self
+                .queue
+                .peek()
+                .is_some_and(|next| next.time < last_side_time)
+            {
+                slop -= 1;
+                if slop == 0 {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn walk_side(&mut self, commit: QueuedCommit<Id>, side: DivergenceSide) -> Result<()> {
+        if self.is_capped(side) {
+            self.paused.push(commit);
+            return Ok(());
+        }
+
+        let node = self.nodes.get_mut(&commit.id).expect("discovered commit");
+        node.counted = true;
+        node.expanded = true;
+        let is_merge = node.parents.len() > 1;
+        let parents = node.parents.clone();
+        self.side_count(side, |count| {
+            if is_merge {
+                count.merges += 1;
+            } else {
+                count.commits += 1;
+            }
+        });
+
+        for parent in parents {
+            if !self.nodes.contains_key(&parent) {
+                self.discover(parent, Mark::Side(side))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Marks the ancestors of a hidden commit as hidden, already counted
+    /// commits are subtracted again.
+    fn hide_parents(&mut self, id: Id) -> Result<()> {
+        let mut pending = vec![id];
+
+        while let Some(id) = pending.pop() {
+            let node = self.nodes.get_mut(&id).expect("discovered commit");
+            if node.expanded {
+                continue;
+            }
+            node.expanded = true;
+
+            for parent in node.parents.clone() {
+                let Some(node) = self.nodes.get_mut(&parent) else {
+                    self.discover(parent, Mark::Hidden)?;
+                    continue;
+                };
+                let Mark::Side(side) = node.mark else {
+                    continue;
+                };
+                node.mark = Mark::Hidden;
+                let was_expanded = std::mem::take(&mut node.expanded);
+                if node.counted {
+                    node.counted = false;
+                    let is_merge = node.parents.len() > 1;
+                    self.side_count(side, |count| {
+                        if is_merge {
+                            count.merges -= 1;
+                        } else {
+                            count.commits -= 1;
+                        }
+                    });
+                    self.resume(side);
+                }
+                if was_expanded {
+                    pending.push(parent);
+                } else {
+                    self.queue.push(QueuedCommit {
+                        time: self.nodes[&parent].time,
+                        hidden: true,
+                        id: parent,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn discover(&mut self, id: Id, mark: Mark) -> Result<()> {
+        if self.nodes.contains_key(&id) {
+            return Ok(());
+        }
+        let CommitInfo { time, parents } = (self.commit_info)(id)?;
+        self.nodes.insert(
+            id,
+            Node {
+                mark,
+                time,
+                parents,
+                expanded: false,
+                counted: false,
+            },
+        );
+        self.enqueue(QueuedCommit {
+            time,
+            hidden: mark == Mark::Hidden,
+            id,
+        });
+        Ok(())
+    }
+
+    fn enqueue(&mut self, commit: QueuedCommit<Id>) {
+        if !commit.hidden {
+            self.interesting += 1;
+        }
+        self.queue.push(commit);
+    }
+
+    /// Walks the paused commits of `side` again once it is below the limit.
+    fn resume(&mut self, side: DivergenceSide) {
+        if self.is_capped(side) {
+            return;
+        }
+        let (resumed, paused) = std::mem::take(&mut self.paused)
+            .into_iter()
+            .partition(|commit| self.nodes[&commit.id].mark == Mark::Side(side));
+        self.paused = paused;
+        for commit in resumed {
+            self.enqueue(commit);
+        }
+    }
+
+    fn side_count(&mut self, side: DivergenceSide, update: impl FnOnce(&mut SideCount)) {
+        match side {
+            DivergenceSide::Pull => update(&mut self.pull),
+            DivergenceSide::Push => update(&mut self.push),
+        }
+    }
+
+    fn is_capped(&self, side: DivergenceSide) -> bool {
+        self.divergence().is_capped(side, self.options)
+    }
+
+    fn divergence(&self) -> Divergence {
+        let (pull, push) = (self.pull, self.push);
+        match self.options.merge_commits {
+            MergeCommits::All => Divergence {
+                to_pull: pull.commits + pull.merges,
+                to_push: push.commits + push.merges,
+                ..Default::default()
+            },
+            MergeCommits::NoMerges => Divergence {
+                to_pull: pull.commits,
+                to_push: push.commits,
+                ..Default::default()
+            },
+            MergeCommits::Both => Divergence {
+                to_pull: pull.commits,
+                to_push: push.commits,
+                merges_to_pull: pull.merges,
+                merges_to_push: push.merges,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A commit graph given as `(id, time, parents)`.
+    fn walk(
+        commits: &[(u32, i64, &[u32])],
+        from: u32,
+        to: u32,
+        merge_bases: &[u32],
+        options: DivergenceOptions,
+    ) -> Divergence {
+        let commits: HashMap<u32, CommitInfo<u32>> = commits
+            .iter()
+            .map(|(id, time, parents)| {
+                (
+                    *id,
+                    CommitInfo {
+                        time: *time,
+                        parents: parents.to_vec(),
+                    },
+                )
+            })
+            .collect();
+
+        walk_divergence(from, to, merge_bases, &options, |id| {
+            let commit = &commits[&id];
+            Ok(CommitInfo {
+                time: commit.time,
+                parents: commit.parents.clone(),
+            })
+        })
+        .unwrap()
+    }
+
+    fn options(merge_commits: MergeCommits, limit: Option<usize>) -> DivergenceOptions {
+        DivergenceOptions {
+            merge_commits,
+            limit,
+        }
+    }
+
+    /// 1 - 2 - 3 - 4 - 7 (from)
+    ///      \         /
+    ///       5 ----- 6 - 8 (to)
+    const MERGED: &[(u32, i64, &[u32])] = &[
+        (1, 1, &[]),
+        (2, 2, &[1]),
+        (3, 3, &[2]),
+        (4, 4, &[3]),
+        (5, 5, &[2]),
+        (6, 6, &[5]),
+        (7, 7, &[4, 6]),
+        (8, 8, &[6]),
+    ];
+
+    #[test]
+    fn counts_both_sides() {
+        let divergence = walk(MERGED, 7, 8, &[6], options(MergeCommits::All, None));
+
+        assert_eq!((divergence.to_pull, divergence.to_push), (3, 1));
+    }
+
+    #[test]
+    fn counts_merges_separately() {
+        let no_merges = walk(MERGED, 7, 8, &[6], options(MergeCommits::NoMerges, None));
+        let both = walk(MERGED, 7, 8, &[6], options(MergeCommits::Both, None));
+
+        assert_eq!((no_merges.to_pull, no_merges.to_push), (2, 1));
+        assert_eq!(
+            both,
+            Divergence {
+                to_pull: 2,
+                to_push: 1,
+                merges_to_pull: 1,
+                merges_to_push: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn uncounts_commits_dated_after_a_hidden_child() {
+        // 3 is dated after its child 2, the merge base, so it is reached from
+        // the pull side first.
+        let skewed: &[(u32, i64, &[u32])] = &[
+            (1, 1, &[]),
+            (3, 8, &[1]),
+            (2, 5, &[3]),
+            (4, 19, &[3]),
+            (5, 20, &[2, 4]),
+            (6, 21, &[2]),
+        ];
+        let divergence = walk(skewed, 5, 6, &[2], options(MergeCommits::All, None));
+
+        assert_eq!((divergence.to_pull, divergence.to_push), (2, 1));
+    }
+
+    #[test]
+    fn stops_at_the_limit_per_side() {
+        let mut line: Vec<(u32, i64, &[u32])> = vec![(0, 0, &[])];
+        let parents: Vec<[u32; 1]> = (0..20).map(|id| [id]).collect();
+        for id in 1..20 {
+            line.push((id, id as i64, &parents[id as usize - 1]));
+        }
+        line.push((100, 100, &[1]));
+
+        let mut walked = 0;
+        let divergence =
+            walk_divergence(19, 100, &[1], &options(MergeCommits::All, Some(5)), |id| {
+                walked += 1;
+                let (_, time, parents) = line.iter().find(|(known, ..)| *known == id).unwrap();
+                Ok(CommitInfo {
+                    time: *time,
+                    parents: parents.to_vec(),
+                })
+            })
+            .unwrap();
+
+        assert_eq!((divergence.to_pull, divergence.to_push), (6, 1));
+        assert!(walked < 15);
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::Result;
+use std::path::PathBuf;
+
+use super::{
+    backend::GitBackend,
+    types::{Divergence, DivergenceOptions, GitLocalRepoChanges, GitRepoState, MergeCommits},
+};
+
+/// In-memory backend answering every query from a prepared `GitRepoState`.
+///
+/// Ahead/behind queries against `HEAD` answer with the local commit counts,
+/// all others with the merge branch counts, merge commits are counted as
+/// selected by the options.
+#[derive(Debug, Default, Clone)]
+pub struct FakeBackend {
+    pub repo_state: GitRepoState,
+    pub git_dir: PathBuf,
+    pub work_dir: Option<PathBuf>,
+    pub remote_default_branch: String,
+    pub merge_base: String,
+    pub config_entries: Vec<(String, String)>,
+    pub tracked_files: Vec<PathBuf>,
+}
+
+impl FakeBackend {
+    pub fn new(repo_state: GitRepoState) -> FakeBackend {
+        let remote_default_branch = format!("{}/master", repo_state.remote);
+
+        Self {
+            repo_state,
+            git_dir: ".git".into(),
+            work_dir: Some(".".into()),
+            remote_default_branch,
+            merge_base: "merge-base".into(),
+            config_entries: Vec::new(),
+            tracked_files: Vec::new(),
+        }
+    }
+}
+
+impl GitBackend for FakeBackend {
+    fn git_dir(&mut self) -> Result<PathBuf> {
+        Ok(self.git_dir.clone())
+    }
+
+    fn work_dir(&mut self) -> Result<Option<PathBuf>> {
+        Ok(self.work_dir.clone())
+    }
+
+    fn local_branch(&mut self) -> Result<String> {
+        Ok(self.repo_state.local_branch.clone())
+    }
+
+    fn commit_short_sha(&mut self) -> Result<String> {
+        Ok(self.repo_state.commit_short_sha.clone())
+    }
+
+    fn commit_tag(&mut self) -> Result<String> {
+        Ok(self.repo_state.commit_tag.clone())
+    }
+
+    fn local_repo_changes(&mut self) -> Result<GitLocalRepoChanges> {
+        Ok(self.repo_state.git_local_repo_changes.clone())
+    }
+
+    fn stash_count(&mut self) -> Result<usize> {
+        Ok(self.repo_state.stash_count)
+    }
+
+    fn tracked_files(&mut self) -> Result<Vec<PathBuf>> {
+        Ok(self.tracked_files.clone())
+    }
+
+    fn remote(&mut self, _local_branch: &str) -> Result<String> {
+        Ok(self.repo_state.remote.clone())
+    }
+
+    fn remote_tracking_branch(&mut self, _local_branch: &str) -> Result<String> {
+        Ok(self.repo_state.remote_tracking_branch.clone())
+    }
+
+    fn remote_default_branch(&mut self, _remote: &str) -> Result<String> {
+        Ok(self.remote_default_branch.clone())
+    }
+
+    fn config_entries(&mut self, section: &str) -> Result<Vec<(String, String)>> {
+        let prefix = format!("{section}.");
+
+        Ok(self
+            .config_entries
+            .iter()
+            .filter(|(name, _)| name.starts_with(&prefix))
+            .cloned()
+            .collect())
+    }
+
+    fn merge_base(&mut self, _remote_default_branch: &str, _local_branch: &str) -> Result<String> {
+        Ok(self.merge_base.clone())
+    }
+
+    fn divergence(
+        &mut self,
+        _from_commit: &str,
+        to_commit: &str,
+        options: &DivergenceOptions,
+    ) -> Result<Divergence> {
+        let repo_state = &self.repo_state;
+        let divergence = if to_commit == "HEAD" {
+            Divergence {
+                to_pull: repo_state.commits_to_pull,
+                to_push: repo_state.commits_to_push,
+                merges_to_pull: repo_state.merges_to_pull,
+                merges_to_push: repo_state.merges_to_push,
+            }
+        } else {
+            Divergence {
+                to_pull: repo_state.merge_branch_commits_to_pull,
+                to_push: repo_state.merge_branch_commits_to_push,
+                merges_to_pull: repo_state.merge_branch_merges_to_pull,
+                merges_to_push: repo_state.merge_branch_merges_to_push,
+            }
+        };
+        let divergence = match options.merge_commits {
+            MergeCommits::All => Divergence {
+                to_pull: divergence.to_pull + divergence.merges_to_pull,
+                to_push: divergence.to_push + divergence.merges_to_push,
+                ..Default::default()
+            },
+            MergeCommits::NoMerges => Divergence {
+                to_pull: divergence.to_pull,
+                to_push: divergence.to_push,
+                ..Default::default()
+            },
+            MergeCommits::Both => divergence,
+        };
+
+        Ok(divergence.capped(options))
+    }
+}
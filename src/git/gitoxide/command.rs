@@ -0,0 +1,218 @@
+use anyhow::{anyhow, Result};
+use gix::{
+    bstr::ByteSlice,
+    diff::index::ChangeRef,
+    progress::Discard,
+    refs::TargetRef,
+    status::{index_worktree::iter::Summary, Item},
+    ObjectId, Repository,
+};
+use std::path::PathBuf;
+
+use crate::git::{
+    divergence::{walk_divergence, CommitInfo},
+    types::{Divergence, DivergenceOptions, GitLocalRepoChanges},
+};
+
+pub fn local_branch_name(repository: &Repository) -> Result<String> {
+    if let Some(head_name) = repository.head_name()? {
+        if let Some(branch_name) = head_name.as_bstr().strip_prefix(b"refs/heads/") {
+            return Ok(branch_name.to_str_lossy().into());
+        }
+    }
+    Ok("".into())
+}
+
+pub fn local_repo_changes(repository: &Repository) -> Result<GitLocalRepoChanges> {
+    let statuses = repository
+        .status(Discard)?
+        .index_worktree_rewrites(None)
+        .into_iter(None)?;
+    let mut result = GitLocalRepoChanges::default();
+
+    for item in statuses {
+        match item? {
+            Item::TreeIndex(change) => match change {
+                ChangeRef::Addition { .. } => result.index_add += 1,
+                ChangeRef::Deletion { .. } => result.index_del += 1,
+                ChangeRef::Modification { .. } => result.index_mod += 1,
+                ChangeRef::Rewrite { .. } => result.renamed += 1,
+            },
+            Item::IndexWorktree(item) => match item.summary() {
+                Some(Summary::Conflict) => result.conflict += 1,
+                Some(Summary::Added | Summary::IntentToAdd) => result.local_add += 1,
+                Some(Summary::Removed) => result.local_del += 1,
+                Some(Summary::Modified | Summary::TypeChange) => result.local_mod += 1,
+                Some(Summary::Renamed | Summary::Copied) | None => (),
+            },
+        }
+    }
+
+    Ok(result)
+}
+
+pub fn remote_default_branch(repository: &Repository, remote: &str) -> Result<String> {
+    if let Some(remote_head) =
+        repository.try_find_reference(format!("refs/remotes/{remote}/HEAD").as_str())?
+    {
+        if let TargetRef::Symbolic(remote_ref) = remote_head.target() {
+            if let Some(remote_branch) = remote_ref.as_bstr().strip_prefix(b"refs/remotes/") {
+                return Ok(remote_branch.to_str_lossy().into());
+            }
+        }
+    }
+    Ok(format!("{remote}/master"))
+}
+
+pub fn merge_base(
+    repository: &Repository,
+    remote_default_branch: &str,
+    local_branch_name: &str,
+) -> Result<String> {
+    let Some(mut remote_ref) =
+        repository.try_find_reference(format!("refs/remotes/{remote_default_branch}").as_str())?
+    else {
+        return Ok("".into());
+    };
+    let remote_oid = remote_ref.peel_to_id_in_place()?;
+    let local_oid = repository
+        .find_reference(format!("refs/heads/{local_branch_name}").as_str())?
+        .peel_to_id_in_place()?;
+
+    match repository.merge_base(remote_oid, local_oid) {
+        Ok(merge_base) => Ok(merge_base.to_string()),
+        Err(gix::repository::merge_base::Error::NotFound { .. }) => Ok("".into()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub fn remote_name(repository: &Repository, local_branch_name: &str) -> Result<String> {
+    config_string(
+        repository,
+        &git_remote_tracking_config_key(local_branch_name),
+    )
+}
+
+pub fn remote_branch_name(repository: &Repository, local_branch_name: &str) -> Result<String> {
+    config_string(repository, &git_remote_branch_config_key(local_branch_name))
+}
+
+pub fn stash_count(repository: &Repository) -> Result<usize> {
+    let Some(stash_ref) = repository.try_find_reference("refs/stash")? else {
+        return Ok(0);
+    };
+    let mut log_iter = stash_ref.log_iter();
+    let count = match log_iter.all()? {
+        Some(entries) => entries.count(),
+        None => 0,
+    };
+
+    Ok(count)
+}
+
+pub fn tracked_files(repository: &Repository) -> Result<Vec<PathBuf>> {
+    let index = repository.index_or_empty()?;
+    Ok(index
+        .entries()
+        .iter()
+        .map(|entry| gix::path::from_bstr(entry.path(&index)).into_owned())
+        .collect())
+}
+
+pub fn commit_short_sha(repository: &Repository) -> Result<String> {
+    match repository.head()?.id() {
+        Some(oid) => Ok(oid.to_hex_with_len(7).to_string()),
+        None => Ok("".into()),
+    }
+}
+
+pub fn commit_tag(repository: &Repository) -> Result<String> {
+    let Some(oid) = repository.head()?.id() else {
+        return Ok("".into());
+    };
+
+    for tag_ref in repository.references()?.tags()? {
+        let mut tag_ref = tag_ref.map_err(|err| anyhow!("{err}"))?;
+        if tag_ref.peel_to_id_in_place()? == oid {
+            return Ok(tag_ref.name().shorten().to_str_lossy().into());
+        }
+    }
+    Ok("".into())
+}
+
+pub fn divergence(
+    repository: &Repository,
+    from_commit: &str,
+    to_commit: &str,
+    options: &DivergenceOptions,
+) -> Result<Divergence> {
+    let from_oid = repository.rev_parse_single(from_commit)?.detach();
+    let to_oid = repository.rev_parse_single(to_commit)?.detach();
+    let merge_bases: Vec<ObjectId> = repository
+        .merge_bases_many(from_oid, &[to_oid])?
+        .into_iter()
+        .map(|merge_base| merge_base.detach())
+        .collect();
+    // Unrelated histories are not compared.
+    if merge_bases.is_empty() {
+        return Ok(Divergence::default());
+    }
+
+    walk_divergence(from_oid, to_oid, &merge_bases, options, |oid| {
+        let commit = repository.find_commit(oid)?;
+        Ok(CommitInfo {
+            time: commit.time()?.seconds,
+            parents: commit.parent_ids().map(|parent| parent.detach()).collect(),
+        })
+    })
+}
+
+pub fn config_entries(repository: &Repository, section: &str) -> Result<Vec<(String, String)>> {
+    let config = repository.config_snapshot();
+    let mut result = Vec::new();
+
+    for config_section in config
+        .plumbing()
+        .sections_by_name(section)
+        .into_iter()
+        .flatten()
+    {
+        let prefix = match config_section.header().subsection_name() {
+            Some(subsection) => format!("{section}.{subsection}"),
+            None => section.into(),
+        };
+        let mut value_names = Vec::new();
+        for value_name in config_section.body().value_names() {
+            let value_name = value_name.as_ref().to_ascii_lowercase();
+            if !value_names.contains(&value_name) {
+                value_names.push(value_name);
+            }
+        }
+        for value_name in value_names {
+            for value in config_section.body().values(&value_name) {
+                result.push((
+                    format!("{prefix}.{value_name}"),
+                    value.to_str_lossy().into(),
+                ));
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+fn config_string(repository: &Repository, key: &str) -> Result<String> {
+    Ok(repository
+        .config_snapshot()
+        .string(key)
+        .map(|value| value.to_str_lossy().into())
+        .unwrap_or_default())
+}
+
+fn git_remote_tracking_config_key(local_branch_name: &str) -> String {
+    format!("branch.{local_branch_name}.remote")
+}
+
+fn git_remote_branch_config_key(local_branch_name: &str) -> String {
+    format!("branch.{local_branch_name}.merge")
+}
@@ -0,0 +1,95 @@
+use anyhow::Result;
+use gix::{discover, Repository, ThreadSafeRepository};
+use std::path::PathBuf;
+
+use self::command::{
+    commit_short_sha, commit_tag, config_entries, divergence, local_branch_name,
+    local_repo_changes, merge_base, remote_branch_name, remote_default_branch, remote_name,
+    stash_count, tracked_files,
+};
+
+use super::{
+    backend::GitBackend,
+    types::{Divergence, DivergenceOptions, GitLocalRepoChanges},
+};
+
+pub mod command;
+
+pub struct GixBackend {
+    repository: Repository,
+}
+
+impl GixBackend {
+    pub fn open() -> Result<Option<GixBackend>> {
+        match ThreadSafeRepository::discover_with_environment_overrides(".") {
+            Ok(repository) => Ok(Some(GixBackend {
+                repository: repository.to_thread_local(),
+            })),
+            Err(discover::Error::Discover(_)) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+impl GitBackend for GixBackend {
+    fn git_dir(&mut self) -> Result<PathBuf> {
+        Ok(self.repository.git_dir().into())
+    }
+
+    fn work_dir(&mut self) -> Result<Option<PathBuf>> {
+        Ok(self.repository.workdir().map(Into::into))
+    }
+
+    fn local_branch(&mut self) -> Result<String> {
+        local_branch_name(&self.repository)
+    }
+
+    fn commit_short_sha(&mut self) -> Result<String> {
+        commit_short_sha(&self.repository)
+    }
+
+    fn commit_tag(&mut self) -> Result<String> {
+        commit_tag(&self.repository)
+    }
+
+    fn local_repo_changes(&mut self) -> Result<GitLocalRepoChanges> {
+        local_repo_changes(&self.repository)
+    }
+
+    fn stash_count(&mut self) -> Result<usize> {
+        stash_count(&self.repository)
+    }
+
+    fn tracked_files(&mut self) -> Result<Vec<PathBuf>> {
+        tracked_files(&self.repository)
+    }
+
+    fn remote(&mut self, local_branch: &str) -> Result<String> {
+        remote_name(&self.repository, local_branch)
+    }
+
+    fn remote_tracking_branch(&mut self, local_branch: &str) -> Result<String> {
+        remote_branch_name(&self.repository, local_branch)
+    }
+
+    fn remote_default_branch(&mut self, remote: &str) -> Result<String> {
+        remote_default_branch(&self.repository, remote)
+    }
+
+    fn config_entries(&mut self, section: &str) -> Result<Vec<(String, String)>> {
+        config_entries(&self.repository, section)
+    }
+
+    fn merge_base(&mut self, remote_default_branch: &str, local_branch: &str) -> Result<String> {
+        merge_base(&self.repository, remote_default_branch, local_branch)
+    }
+
+    fn divergence(
+        &mut self,
+        from_commit: &str,
+        to_commit: &str,
+        options: &DivergenceOptions,
+    ) -> Result<Divergence> {
+        divergence(&self.repository, from_commit, to_commit, options)
+    }
+}
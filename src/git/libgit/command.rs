@@ -1,7 +1,11 @@
 use anyhow::Result;
-use git2::{ErrorCode, Repository, Sort, StatusOptions};
+use git2::{ErrorCode, Repository, StatusOptions};
+use std::path::PathBuf;
 
-use crate::git::types::GitLocalRepoChanges;
+use crate::git::{
+    divergence::{walk_divergence, CommitInfo},
+    types::{Divergence, DivergenceOptions, GitLocalRepoChanges, MergeCommits},
+};
 
 macro_rules! ignore_error_code {
     ($error_code: ident, $stmt: expr, $fallback: expr) => {
@@ -76,6 +80,21 @@ pub fn remote_default_branch(repository: &Repository, remote: &str) -> Result<St
     Ok(format!("{remote}/master"))
 }
 
+pub fn config_entries(repository: &Repository, section: &str) -> Result<Vec<(String, String)>> {
+    let config = repository.config()?;
+    let mut entries = config.entries(Some(&format!("^{section}\\.")))?;
+    let mut result = Vec::new();
+
+    while let Some(entry) = entries.next() {
+        let entry = entry?;
+        if let Some(name) = entry.name() {
+            result.push((name.into(), entry.value().unwrap_or("true").into()));
+        }
+    }
+
+    Ok(result)
+}
+
 pub fn merge_base(
     repository: &Repository,
     remote_default_branch: &str,
@@ -122,6 +141,14 @@ pub fn stash_count(repository: &mut Repository) -> Result<usize> {
     Ok(count)
 }
 
+pub fn tracked_files(repository: &Repository) -> Result<Vec<PathBuf>> {
+    Ok(repository
+        .index()?
+        .iter()
+        .map(|entry| String::from_utf8_lossy(&entry.path).into_owned().into())
+        .collect())
+}
+
 pub fn commit_short_sha(repository: &Repository) -> Result<String> {
     let head = ignore_error_code!(UnbornBranch, repository.head());
     let head_commit = head.peel_to_commit()?;
@@ -148,48 +175,38 @@ pub fn commit_tag(repository: &Repository) -> Result<String> {
     Ok("".into())
 }
 
-pub fn rev_to_push(repository: &Repository, from_commit: &str, to_commit: &str) -> Result<usize> {
+pub fn divergence(
+    repository: &Repository,
+    from_commit: &str,
+    to_commit: &str,
+    options: &DivergenceOptions,
+) -> Result<Divergence> {
     let from_oid = repository.revparse_single(from_commit)?.id();
     let to_oid = repository.revparse_single(to_commit)?.id();
 
-    let mut revwalk = repository.revwalk()?;
-    revwalk.set_sorting(Sort::TOPOLOGICAL)?;
-    revwalk.push(to_oid)?;
-    revwalk.hide(from_oid)?;
-
-    let mut count = 0;
+    if options.merge_commits == MergeCommits::All && options.limit.is_none() {
+        let (to_push, to_pull) = repository.graph_ahead_behind(to_oid, from_oid)?;
 
-    for oid in revwalk {
-        let commit = repository.find_commit(oid?)?;
-
-        if commit.parent_count() == 1 {
-            count += 1;
-        }
+        return Ok(Divergence {
+            to_pull,
+            to_push,
+            ..Default::default()
+        });
     }
 
-    Ok(count)
-}
-
-pub fn rev_to_pull(repository: &Repository, from_commit: &str, to_commit: &str) -> Result<usize> {
-    let from_oid = repository.revparse_single(from_commit)?.id();
-    let to_oid = repository.revparse_single(to_commit)?.id();
-
-    let mut revwalk = repository.revwalk()?;
-    revwalk.set_sorting(Sort::TOPOLOGICAL)?;
-    revwalk.push(from_oid)?;
-    revwalk.hide(to_oid)?;
-
-    let mut count = 0;
-
-    for oid in revwalk {
-        let commit = repository.find_commit(oid?)?;
-
-        if commit.parent_count() == 1 {
-            count += 1;
-        }
-    }
+    let merge_bases = ignore_error_code!(
+        NotFound,
+        repository.merge_bases(from_oid, to_oid),
+        Divergence::default()
+    );
 
-    Ok(count)
+    walk_divergence(from_oid, to_oid, &merge_bases, options, |oid| {
+        let commit = repository.find_commit(oid)?;
+        Ok(CommitInfo {
+            time: commit.time().seconds(),
+            parents: commit.parent_ids().collect(),
+        })
+    })
 }
 
 fn git_remote_tracking_config_key(local_branch_name: &str) -> String {
@@ -1,73 +1,93 @@
 use anyhow::Result;
 use git2::{ErrorCode, Repository};
+use std::path::PathBuf;
 
 use self::command::{
-    commit_short_sha, commit_tag, local_branch_name, local_repo_changes, merge_base,
-    remote_branch_name, remote_default_branch, remote_name, rev_to_pull, rev_to_push, stash_count,
+    commit_short_sha, commit_tag, config_entries, divergence, local_branch_name,
+    local_repo_changes, merge_base, remote_branch_name, remote_default_branch, remote_name,
+    stash_count, tracked_files,
 };
 
-use super::{branch::build_fully_qualified_remote_branch_name, types::GitRepoState};
+use super::{
+    backend::GitBackend,
+    types::{Divergence, DivergenceOptions, GitLocalRepoChanges},
+};
 
 pub mod command;
 
-pub fn check_in_git_directory() -> Result<bool> {
-    match Repository::open_from_env() {
-        Ok(_) => Ok(true),
-        Err(err) if err.code() == ErrorCode::NotFound => Ok(false),
-        Err(err) => Err(err.into()),
-    }
+pub struct LibGitBackend {
+    repository: Repository,
 }
 
-pub fn get_git_repo_state() -> Result<GitRepoState> {
-    let mut repository = Repository::open_from_env()?;
-    let local_branch = local_branch_name(&repository)?;
-    let git_local_repo_changes = local_repo_changes(&repository)?;
-    let remote = remote_name(&repository, &local_branch)?;
-    let stash_count = stash_count(&mut repository)?;
-    let commit_short_sha = commit_short_sha(&repository)?;
-    let commit_tag = commit_tag(&repository)?;
-
-    let mut repo_state = GitRepoState {
-        local_branch,
-        git_local_repo_changes,
-        remote,
-        stash_count,
-        commit_short_sha,
-        commit_tag,
-        ..Default::default()
-    };
-
-    if !repo_state.remote.is_empty() {
-        repo_state.remote_tracking_branch =
-            remote_branch_name(&repository, &repo_state.local_branch)?;
-        let remote_default_branch = remote_default_branch(&repository, &repo_state.remote)?;
-        let merge_base = merge_base(
-            &repository,
-            &remote_default_branch,
-            &repo_state.local_branch,
-        )?;
-
-        let full_remote_branch_name = build_fully_qualified_remote_branch_name(
-            &repo_state.remote,
-            &repo_state.remote_tracking_branch,
-        );
-
-        repo_state.commits_to_pull = rev_to_pull(&repository, &full_remote_branch_name, "HEAD")?;
-        repo_state.commits_to_push = rev_to_push(&repository, &full_remote_branch_name, "HEAD")?;
-
-        if !merge_base.is_empty() {
-            repo_state.merge_branch_commits_to_pull = rev_to_pull(
-                &repository,
-                &remote_default_branch,
-                &full_remote_branch_name,
-            )?;
-            repo_state.merge_branch_commits_to_push = rev_to_push(
-                &repository,
-                &remote_default_branch,
-                &full_remote_branch_name,
-            )?;
+impl LibGitBackend {
+    pub fn open() -> Result<Option<LibGitBackend>> {
+        match Repository::open_from_env() {
+            Ok(repository) => Ok(Some(LibGitBackend { repository })),
+            Err(err) if err.code() == ErrorCode::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
         }
     }
+}
+
+impl GitBackend for LibGitBackend {
+    fn git_dir(&mut self) -> Result<PathBuf> {
+        Ok(self.repository.path().into())
+    }
+
+    fn work_dir(&mut self) -> Result<Option<PathBuf>> {
+        Ok(self.repository.workdir().map(Into::into))
+    }
+
+    fn local_branch(&mut self) -> Result<String> {
+        local_branch_name(&self.repository)
+    }
+
+    fn commit_short_sha(&mut self) -> Result<String> {
+        commit_short_sha(&self.repository)
+    }
+
+    fn commit_tag(&mut self) -> Result<String> {
+        commit_tag(&self.repository)
+    }
+
+    fn local_repo_changes(&mut self) -> Result<GitLocalRepoChanges> {
+        local_repo_changes(&self.repository)
+    }
 
-    Ok(repo_state)
+    fn stash_count(&mut self) -> Result<usize> {
+        stash_count(&mut self.repository)
+    }
+
+    fn tracked_files(&mut self) -> Result<Vec<PathBuf>> {
+        tracked_files(&self.repository)
+    }
+
+    fn remote(&mut self, local_branch: &str) -> Result<String> {
+        remote_name(&self.repository, local_branch)
+    }
+
+    fn remote_tracking_branch(&mut self, local_branch: &str) -> Result<String> {
+        remote_branch_name(&self.repository, local_branch)
+    }
+
+    fn remote_default_branch(&mut self, remote: &str) -> Result<String> {
+        remote_default_branch(&self.repository, remote)
+    }
+
+    fn config_entries(&mut self, section: &str) -> Result<Vec<(String, String)>> {
+        config_entries(&self.repository, section)
+    }
+
+    fn merge_base(&mut self, remote_default_branch: &str, local_branch: &str) -> Result<String> {
+        merge_base(&self.repository, remote_default_branch, local_branch)
+    }
+
+    fn divergence(
+        &mut self,
+        from_commit: &str,
+        to_commit: &str,
+        options: &DivergenceOptions,
+    ) -> Result<Divergence> {
+        divergence(&self.repository, from_commit, to_commit, options)
+    }
 }
@@ -1,11 +1,257 @@
+use anyhow::{anyhow, Result};
+use std::{
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use self::{
+    backend::GitBackend,
+    branch::build_fully_qualified_remote_branch_name,
+    types::{DivergenceOptions, GitQueries, GitRepoState, GitStateUpdate},
+};
+
+pub mod backend;
 pub mod branch;
-#[cfg(not(feature = "libgit"))]
+pub mod cache;
 pub mod cli;
+#[cfg(any(feature = "libgit", feature = "gix"))]
+pub mod divergence;
+pub mod fake;
+#[cfg(feature = "gix")]
+pub mod gitoxide;
 #[cfg(feature = "libgit")]
 pub mod libgit;
 pub mod types;
 
-#[cfg(not(feature = "libgit"))]
-pub use cli::{check_in_git_directory, get_git_repo_state};
-#[cfg(feature = "libgit")]
-pub use libgit::{check_in_git_directory, get_git_repo_state};
+#[cfg(feature = "gix")]
+pub fn open_backend() -> Result<Option<Box<dyn GitBackend>>> {
+    Ok(gitoxide::GixBackend::open()?.map(|backend| Box::new(backend) as Box<dyn GitBackend>))
+}
+
+#[cfg(all(feature = "libgit", not(feature = "gix")))]
+pub fn open_backend() -> Result<Option<Box<dyn GitBackend>>> {
+    Ok(libgit::LibGitBackend::open()?.map(|backend| Box::new(backend) as Box<dyn GitBackend>))
+}
+
+#[cfg(not(any(feature = "libgit", feature = "gix")))]
+pub fn open_backend() -> Result<Option<Box<dyn GitBackend>>> {
+    Ok(cli::CliBackend::open()?.map(|backend| Box::new(backend) as Box<dyn GitBackend>))
+}
+
+pub fn get_git_repo_state(
+    mut backend: Box<dyn GitBackend>,
+    queries: GitQueries,
+    time_budget: Option<Duration>,
+) -> Result<GitRepoState> {
+    let deadline = time_budget.map(|time_budget| Instant::now() + time_budget);
+    let repo_state = initial_git_repo_state(backend.as_mut(), queries)?;
+
+    complete_git_repo_state(backend, repo_state, queries.divergence, deadline)
+}
+
+/// The cheap part of the state, the expensive values are marked unknown
+/// until `complete_git_repo_state` queries them.
+pub fn initial_git_repo_state(
+    backend: &mut dyn GitBackend,
+    queries: GitQueries,
+) -> Result<GitRepoState> {
+    let local_branch = backend.local_branch()?;
+    let remote = backend.remote(&local_branch)?;
+    let commit_short_sha = backend.commit_short_sha()?;
+
+    let mut repo_state = GitRepoState {
+        local_branch,
+        remote,
+        commit_short_sha,
+        ..Default::default()
+    };
+
+    if !repo_state.remote.is_empty() {
+        repo_state.remote_tracking_branch =
+            backend.remote_tracking_branch(&repo_state.local_branch)?;
+        if queries.merge_branch_commits {
+            repo_state.merge_branch = backend.remote_default_branch(&repo_state.remote)?;
+        }
+        repo_state.unknown.commits_to_origin = queries.commits_to_origin;
+        repo_state.unknown.merge_branch_commits = queries.merge_branch_commits;
+    }
+    repo_state.unknown.stash_count = queries.stash_count;
+    repo_state.unknown.commit_tag = queries.commit_tag;
+    repo_state.unknown.local_repo_changes = queries.local_repo_changes;
+
+    Ok(repo_state)
+}
+
+pub fn complete_git_repo_state(
+    mut backend: Box<dyn GitBackend>,
+    mut repo_state: GitRepoState,
+    options: DivergenceOptions,
+    deadline: Option<Instant>,
+) -> Result<GitRepoState> {
+    let query_state = repo_state.clone();
+    let Some(deadline) = deadline else {
+        collect_expensive_state(backend.as_mut(), &query_state, &options, |update| {
+            repo_state.apply(update?);
+            Ok(())
+        })?;
+        return Ok(repo_state);
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        collect_expensive_state(backend.as_mut(), &query_state, &options, |update| {
+            sender.send(update).map_err(|err| anyhow!("{err}"))
+        })
+    });
+
+    loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(timeout) {
+            Ok(update) => repo_state.apply(update?),
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    Ok(repo_state)
+}
+
+fn collect_expensive_state<F>(
+    backend: &mut dyn GitBackend,
+    repo_state: &GitRepoState,
+    options: &DivergenceOptions,
+    mut on_update: F,
+) -> Result<()>
+where
+    F: FnMut(Result<GitStateUpdate>) -> Result<()>,
+{
+    let full_remote_branch_name = build_fully_qualified_remote_branch_name(
+        &repo_state.remote,
+        &repo_state.remote_tracking_branch,
+    );
+
+    if repo_state.unknown.stash_count {
+        on_update(backend.stash_count().map(GitStateUpdate::StashCount))?;
+    }
+
+    if repo_state.unknown.commit_tag {
+        on_update(backend.commit_tag().map(GitStateUpdate::CommitTag))?;
+    }
+
+    if repo_state.unknown.commits_to_origin {
+        on_update(commits_to_origin(
+            backend,
+            &full_remote_branch_name,
+            options,
+        ))?;
+    }
+
+    if repo_state.unknown.local_repo_changes {
+        on_update(
+            backend
+                .local_repo_changes()
+                .map(GitStateUpdate::LocalRepoChanges),
+        )?;
+    }
+
+    if repo_state.unknown.merge_branch_commits {
+        on_update(merge_branch_commits(
+            backend,
+            repo_state,
+            &full_remote_branch_name,
+            options,
+        ))?;
+    }
+
+    Ok(())
+}
+
+fn commits_to_origin(
+    backend: &mut dyn GitBackend,
+    full_remote_branch_name: &str,
+    options: &DivergenceOptions,
+) -> Result<GitStateUpdate> {
+    Ok(GitStateUpdate::CommitsToOrigin(backend.divergence(
+        full_remote_branch_name,
+        "HEAD",
+        options,
+    )?))
+}
+
+fn merge_branch_commits(
+    backend: &mut dyn GitBackend,
+    repo_state: &GitRepoState,
+    full_remote_branch_name: &str,
+    options: &DivergenceOptions,
+) -> Result<GitStateUpdate> {
+    let merge_branch = &repo_state.merge_branch;
+    let merge_base = backend.merge_base(merge_branch, &repo_state.local_branch)?;
+
+    if merge_base.is_empty() {
+        return Ok(GitStateUpdate::MergeBranchCommits(Default::default()));
+    }
+
+    Ok(GitStateUpdate::MergeBranchCommits(backend.divergence(
+        merge_branch,
+        full_remote_branch_name,
+        options,
+    )?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fake::FakeBackend, *};
+
+    fn fake_backend(remote: &str) -> FakeBackend {
+        FakeBackend::new(GitRepoState {
+            local_branch: "feature".into(),
+            remote: remote.into(),
+            remote_tracking_branch: "feature".into(),
+            stash_count: 2,
+            commit_tag: "v1".into(),
+            commits_to_push: 1,
+            merge_branch_commits_to_pull: 3,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn initial_state_marks_expensive_values_unknown() {
+        let repo_state =
+            initial_git_repo_state(&mut fake_backend("origin"), GitQueries::all()).unwrap();
+        let unknown = repo_state.unknown;
+
+        assert_eq!(repo_state.merge_branch, "origin/master");
+        assert!(unknown.stash_count && unknown.commit_tag && unknown.local_repo_changes);
+        assert!(unknown.commits_to_origin && unknown.merge_branch_commits);
+        assert_eq!(repo_state.stash_count, 0);
+    }
+
+    #[test]
+    fn commits_are_not_queried_without_remote() {
+        let repo_state = initial_git_repo_state(&mut fake_backend(""), GitQueries::all()).unwrap();
+
+        assert!(repo_state.merge_branch.is_empty());
+        assert!(!repo_state.unknown.commits_to_origin);
+        assert!(!repo_state.unknown.merge_branch_commits);
+    }
+
+    #[test]
+    fn completes_the_queried_values() {
+        let queries = GitQueries {
+            stash_count: true,
+            merge_branch_commits: true,
+            ..Default::default()
+        };
+        let repo_state =
+            get_git_repo_state(Box::new(fake_backend("origin")), queries, None).unwrap();
+
+        assert_eq!(repo_state.stash_count, 2);
+        assert_eq!(repo_state.merge_branch_commits_to_pull, 3);
+        assert_eq!(
+            (repo_state.commit_tag.as_str(), repo_state.commits_to_push),
+            ("", 0)
+        );
+        assert!(!repo_state.unknown.stash_count && !repo_state.unknown.merge_branch_commits);
+    }
+}
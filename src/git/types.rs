@@ -1,3 +1,5 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use std::ops;
 
 #[derive(Debug, Clone, Copy)]
@@ -13,7 +15,7 @@ pub enum GitFileState {
     Skip,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct GitLocalRepoChanges {
     pub local_mod: usize,
     pub local_add: usize,
@@ -62,7 +64,117 @@ impl ops::Add for GitLocalRepoChanges {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum MergeCommits {
+    /// Count all commits.
+    All,
+    /// Only count non-merge commits.
+    #[default]
+    NoMerges,
+    /// Count non-merge commits and show the merge commits in parentheses.
+    Both,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DivergenceOptions {
+    pub merge_commits: MergeCommits,
+    pub limit: Option<usize>,
+}
+
+impl DivergenceOptions {
+    pub fn cap(&self, count: usize) -> usize {
+        match self.limit {
+            Some(limit) => count.min(limit + 1),
+            None => count,
+        }
+    }
+
+    pub fn is_capped(&self, count: usize) -> bool {
+        self.limit.is_some_and(|limit| count > limit)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DivergenceSide {
+    Pull,
+    Push,
+}
+
+/// Commits only on the pull or push side. With `MergeCommits::Both` merge
+/// commits are counted separately in `merges_to_pull`/`merges_to_push`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Divergence {
+    pub to_pull: usize,
+    pub to_push: usize,
+    pub merges_to_pull: usize,
+    pub merges_to_push: usize,
+}
+
+impl Divergence {
+    /// Whether the counts of `side` exceed the limit, so that its remaining
+    /// commits need not be walked.
+    pub fn is_capped(&self, side: DivergenceSide, options: &DivergenceOptions) -> bool {
+        let (commits, merges) = match side {
+            DivergenceSide::Pull => (self.to_pull, self.merges_to_pull),
+            DivergenceSide::Push => (self.to_push, self.merges_to_push),
+        };
+        options.is_capped(commits)
+            && (options.merge_commits != MergeCommits::Both || options.is_capped(merges))
+    }
+
+    pub fn capped(self, options: &DivergenceOptions) -> Divergence {
+        Divergence {
+            to_pull: options.cap(self.to_pull),
+            to_push: options.cap(self.to_push),
+            merges_to_pull: options.cap(self.merges_to_pull),
+            merges_to_push: options.cap(self.merges_to_push),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GitQueries {
+    pub commit_tag: bool,
+    pub stash_count: bool,
+    pub local_repo_changes: bool,
+    pub commits_to_origin: bool,
+    pub merge_branch_commits: bool,
+    pub divergence: DivergenceOptions,
+}
+
+impl GitQueries {
+    pub fn all() -> GitQueries {
+        Self {
+            commit_tag: true,
+            stash_count: true,
+            local_repo_changes: true,
+            commits_to_origin: true,
+            merge_branch_commits: true,
+            divergence: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct GitUnknownState {
+    pub stash_count: bool,
+    pub commit_tag: bool,
+    pub local_repo_changes: bool,
+    pub commits_to_origin: bool,
+    pub merge_branch_commits: bool,
+}
+
+#[derive(Debug)]
+pub enum GitStateUpdate {
+    StashCount(usize),
+    CommitTag(String),
+    LocalRepoChanges(GitLocalRepoChanges),
+    CommitsToOrigin(Divergence),
+    MergeBranchCommits(Divergence),
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct GitRepoState {
     pub git_local_repo_changes: GitLocalRepoChanges,
     pub local_branch: String,
@@ -70,9 +182,126 @@ pub struct GitRepoState {
     pub commit_tag: String,
     pub remote: String,
     pub remote_tracking_branch: String,
+    pub merge_branch: String,
     pub stash_count: usize,
     pub commits_to_pull: usize,
     pub commits_to_push: usize,
+    pub merges_to_pull: usize,
+    pub merges_to_push: usize,
     pub merge_branch_commits_to_pull: usize,
     pub merge_branch_commits_to_push: usize,
+    pub merge_branch_merges_to_pull: usize,
+    pub merge_branch_merges_to_push: usize,
+    pub unknown: GitUnknownState,
+}
+
+impl GitRepoState {
+    pub fn apply(&mut self, update: GitStateUpdate) {
+        match update {
+            GitStateUpdate::StashCount(stash_count) => {
+                self.stash_count = stash_count;
+                self.unknown.stash_count = false;
+            }
+            GitStateUpdate::CommitTag(commit_tag) => {
+                self.commit_tag = commit_tag;
+                self.unknown.commit_tag = false;
+            }
+            GitStateUpdate::LocalRepoChanges(git_local_repo_changes) => {
+                self.git_local_repo_changes = git_local_repo_changes;
+                self.unknown.local_repo_changes = false;
+            }
+            GitStateUpdate::CommitsToOrigin(divergence) => {
+                self.commits_to_pull = divergence.to_pull;
+                self.commits_to_push = divergence.to_push;
+                self.merges_to_pull = divergence.merges_to_pull;
+                self.merges_to_push = divergence.merges_to_push;
+                self.unknown.commits_to_origin = false;
+            }
+            GitStateUpdate::MergeBranchCommits(divergence) => {
+                self.merge_branch_commits_to_pull = divergence.to_pull;
+                self.merge_branch_commits_to_push = divergence.to_push;
+                self.merge_branch_merges_to_pull = divergence.merges_to_pull;
+                self.merge_branch_merges_to_push = divergence.merges_to_push;
+                self.unknown.merge_branch_commits = false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(merge_commits: MergeCommits, limit: Option<usize>) -> DivergenceOptions {
+        DivergenceOptions {
+            merge_commits,
+            limit,
+        }
+    }
+
+    #[test]
+    fn cap_keeps_counts_without_limit() {
+        let options = options(MergeCommits::All, None);
+
+        assert_eq!(options.cap(1000), 1000);
+        assert!(!options.is_capped(1000));
+    }
+
+    #[test]
+    fn cap_stops_one_beyond_the_limit() {
+        let options = options(MergeCommits::All, Some(5));
+
+        assert_eq!(options.cap(5), 5);
+        assert_eq!(options.cap(6), 6);
+        assert_eq!(options.cap(100), 6);
+        assert!(!options.is_capped(5));
+        assert!(options.is_capped(6));
+    }
+
+    #[test]
+    fn divergence_is_capped_per_side() {
+        let options = options(MergeCommits::NoMerges, Some(2));
+        let divergence = Divergence {
+            to_pull: 3,
+            to_push: 1,
+            ..Default::default()
+        };
+
+        assert!(divergence.is_capped(DivergenceSide::Pull, &options));
+        assert!(!divergence.is_capped(DivergenceSide::Push, &options));
+    }
+
+    #[test]
+    fn divergence_with_separate_merges_is_capped_once_both_are() {
+        let options = options(MergeCommits::Both, Some(2));
+        let mut divergence = Divergence {
+            to_pull: 3,
+            merges_to_pull: 1,
+            ..Default::default()
+        };
+        assert!(!divergence.is_capped(DivergenceSide::Pull, &options));
+
+        divergence.merges_to_pull = 3;
+        assert!(divergence.is_capped(DivergenceSide::Pull, &options));
+    }
+
+    #[test]
+    fn capped_divergence() {
+        let divergence = Divergence {
+            to_pull: 10,
+            to_push: 2,
+            merges_to_pull: 4,
+            merges_to_push: 0,
+        };
+
+        assert_eq!(
+            divergence.capped(&options(MergeCommits::Both, Some(3))),
+            Divergence {
+                to_pull: 4,
+                to_push: 2,
+                merges_to_pull: 4,
+                merges_to_push: 0,
+            }
+        );
+    }
 }
@@ -1,46 +1,256 @@
 pub mod config;
+pub mod custom;
+#[cfg(feature = "daemon")]
+pub mod daemon;
 pub mod git;
 pub mod terminal;
 
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{bail, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 use terminal::types::Shell;
 
 use crate::{
-    config::get_app_config,
-    git::{check_in_git_directory, get_git_repo_state},
+    config::{
+        check::{check_app_config, Severity},
+        edit::{get_config_value, set_config_value},
+        fallback_config, get_app_config, global_config_file,
+        init::{default_config_template, init_config},
+        schema::config_schema,
+        show_app_config,
+        theme::Theme,
+        ConfigOptions,
+    },
+    custom::run_custom_segments,
+    git::{cache::get_cached_git_repo_state, get_git_repo_state, open_backend},
     terminal::prompt::Prompt,
 };
 
 #[derive(Parser)]
 #[clap(name = "git-radar-rs", version = clap::crate_version!())]
 struct Args {
-    #[arg(long)]
+    /// Read the configuration from this file instead of the global config.toml
+    #[arg(long, global = true, env = "GIT_RADAR_CONFIG")]
+    config: Option<PathBuf>,
+    /// Use this theme instead of the one of the configuration
+    #[arg(long, global = true, value_enum)]
+    theme: Option<Theme>,
+    /// Print the effective configuration with the source of each value
+    #[arg(long, global = true)]
     show_config: bool,
     #[arg(value_enum, default_value = "other")]
     shell: Shell,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a daemon caching the repository states (requires the "daemon" feature)
+    Daemon,
+    /// Check the configuration for errors, unknown and deprecated keys
+    CheckConfig,
+    /// Write a commented default configuration to the config file
+    InitConfig {
+        /// Overwrite an existing config file
+        #[arg(long)]
+        force: bool,
+        /// Print the configuration to stdout instead
+        #[arg(long)]
+        stdout: bool,
+    },
+    /// Print the JSON Schema of the config file
+    Schema,
+    /// List the available themes
+    Themes,
+    /// Read or change single configuration values
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the effective value of a dotted key (e.g. stash_suffix.color)
+    Get { key: String },
+    /// Set a dotted key in the config file
+    Set { key: String, value: String },
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
+    let options = ConfigOptions {
+        config_file: args.config.as_deref(),
+        theme: args.theme,
+        shell: Some(args.shell),
+    };
+
+    match args.command {
+        Some(Command::Daemon) => return run_daemon(),
+        Some(Command::CheckConfig) => return check_config(options.config_file),
+        Some(Command::InitConfig { force, stdout }) => {
+            return write_default_config(args.config, force, stdout)
+        }
+        Some(Command::Schema) => {
+            println!("{}", serde_json::to_string_pretty(&config_schema())?);
+            return Ok(());
+        }
+        Some(Command::Themes) => {
+            list_themes();
+            return Ok(());
+        }
+        Some(Command::Config { command }) => return edit_config(options, command),
+        None => (),
+    }
+
+    let mut backend = open_backend()?;
 
     if args.show_config {
-        let config = get_app_config()?;
+        print!(
+            "{}",
+            show_app_config(
+                options,
+                backend.as_mut().map(|backend| backend.as_mut() as _)
+            )?
+        );
 
-        println!("{}", toml::to_string(&config)?);
+        return Ok(());
+    }
 
+    let Some(mut backend) = backend else {
         return Ok(());
+    };
+    let (config, config_error) = match get_app_config(options, Some(backend.as_mut())) {
+        Ok(config) => (config, false),
+        Err(_) => (fallback_config(options), true),
+    };
+    let time_budget =
+        (config.time_budget_ms > 0).then(|| Duration::from_millis(config.time_budget_ms));
+    let work_dir = backend.work_dir().ok().flatten();
+    // Placeholders hidden on the branch are not queried
+    let queries = config.git_queries(&backend.local_branch()?);
+
+    #[cfg(feature = "daemon")]
+    let time_budget = {
+        let started = std::time::Instant::now();
+        match daemon::client::query_daemon(queries, time_budget) {
+            daemon::DaemonAnswer::RepoState(repo_state) => {
+                let custom_segments =
+                    run_custom_segments(&config, &repo_state, work_dir.as_deref());
+                print!(
+                    "{}",
+                    Prompt::new(args.shell, config, *repo_state)
+                        .with_config_error(config_error)
+                        .with_custom_segments(custom_segments)
+                );
+                return Ok(());
+            }
+            daemon::DaemonAnswer::NotInRepository => return Ok(()),
+            daemon::DaemonAnswer::Unavailable => (),
+        }
+        // The direct computation only gets what is left of the budget
+        time_budget.map(|time_budget| time_budget.saturating_sub(started.elapsed()))
+    };
+
+    let repo_state = if config.state_cache {
+        get_cached_git_repo_state(backend, queries, time_budget)?
+    } else {
+        get_git_repo_state(backend, queries, time_budget)?
+    };
+    let custom_segments = run_custom_segments(&config, &repo_state, work_dir.as_deref());
+    let prompt = Prompt::new(args.shell, config, repo_state)
+        .with_config_error(config_error)
+        .with_custom_segments(custom_segments);
+
+    print!("{prompt}");
+
+    Ok(())
+}
+
+fn check_config(config_file: Option<&Path>) -> Result<()> {
+    let mut backend = open_backend()?;
+    let diagnostics = check_app_config(
+        config_file,
+        backend.as_mut().map(|backend| backend.as_mut() as _),
+    )?;
+
+    for diagnostic in &diagnostics {
+        println!("{diagnostic}");
     }
 
-    if !check_in_git_directory()? {
+    let errors = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .count();
+    if errors > 0 {
+        bail!("{errors} error(s) in the configuration");
+    }
+    if diagnostics.is_empty() {
+        println!("Configuration is valid");
+    }
+
+    Ok(())
+}
+
+fn write_default_config(config_file: Option<PathBuf>, force: bool, stdout: bool) -> Result<()> {
+    if stdout {
+        print!("{}", default_config_template()?);
         return Ok(());
     }
+    let Some(config_file) = config_file.or_else(global_config_file) else {
+        bail!("No config directory found, use --config <file>");
+    };
 
-    let config = get_app_config()?;
-    let repo_state = get_git_repo_state()?;
-    let prompt = Prompt::new(args.shell, config, repo_state);
+    init_config(&config_file, force)?;
+    println!("Wrote {}", config_file.display());
 
-    print!("{prompt}");
+    Ok(())
+}
+
+fn edit_config(options: ConfigOptions, command: ConfigCommand) -> Result<()> {
+    match command {
+        ConfigCommand::Get { key } => {
+            let mut backend = open_backend()?;
+            let value = get_config_value(
+                options,
+                backend.as_mut().map(|backend| backend.as_mut() as _),
+                &key,
+            )?;
+            println!("{value}");
+        }
+        ConfigCommand::Set { key, value } => {
+            let config_file = options.config_file.map(Path::to_path_buf);
+            let Some(config_file) = config_file.or_else(global_config_file) else {
+                bail!("No config directory found, use --config <file>");
+            };
+            set_config_value(&config_file, &key, &value)?;
+        }
+    }
 
     Ok(())
 }
+
+fn list_themes() {
+    for theme in Theme::value_variants() {
+        println!("{:<10} {}", theme.name(), theme.description());
+    }
+    for theme in Theme::value_variants() {
+        for alias in theme.aliases() {
+            println!("{alias:<10} Alias of {}", theme.name());
+        }
+    }
+}
+
+#[cfg(feature = "daemon")]
+fn run_daemon() -> Result<()> {
+    daemon::server::run_daemon()
+}
+
+#[cfg(not(feature = "daemon"))]
+fn run_daemon() -> Result<()> {
+    bail!("git-radar-rs was compiled without the \"daemon\" feature")
+}
@@ -2,6 +2,7 @@ use self::types::{Color, Shell};
 
 pub mod output;
 pub mod prompt;
+pub mod template;
 pub mod types;
 
 pub fn tell_string_in_color(shell: Shell, color: Color, str: &str) -> String {
@@ -15,15 +16,15 @@ pub fn tell_string_in_color(shell: Shell, color: Color, str: &str) -> String {
 
 fn start_color_marker(shell: Shell, color: Color) -> String {
     match shell {
-        Shell::Tmux => color.tmux_start_code().into(),
+        Shell::Tmux => color.tmux_start_code(),
         Shell::None => "".into(),
-        _ => apply_shell_markers(shell, color.terminal_start_code()),
+        _ => apply_shell_markers(shell, &color.terminal_start_code()),
     }
 }
 
 fn end_color_marker(shell: Shell) -> String {
     match shell {
-        Shell::Tmux => "#[fg=default]".into(),
+        Shell::Tmux => "#[default]".into(),
         Shell::None => "".into(),
         _ => apply_shell_markers(shell, "\x1b[0;39m"),
     }
@@ -5,7 +5,16 @@ use super::types::{Color, ColoredTag, Shell};
 pub struct TerminalOutput<W> {
     shell: Shell,
     writer: W,
-    add_delimiter: bool,
+    /// Written before the next output, dropped if nothing follows.
+    delimiter: Option<String>,
+    /// The delimiter of `add_delimter`.
+    spacing: String,
+    /// Number of non-empty texts written so far.
+    written: usize,
+    /// Style of the uncolored text of the current segment, started lazily
+    /// before its first text.
+    base_color: Option<Color>,
+    base_color_started: bool,
 }
 
 impl<W> TerminalOutput<W>
@@ -16,12 +25,25 @@ where
         Self {
             shell,
             writer,
-            add_delimiter: false,
+            delimiter: None,
+            spacing: " ".into(),
+            written: 0,
+            base_color: None,
+            base_color_started: false,
         }
     }
 
+    pub fn with_spacing(mut self, spacing: &str) -> Self {
+        self.spacing = spacing.into();
+        self
+    }
+
     pub fn string_in_color(&mut self, color: Color, str: &str) -> fmt::Result {
+        if str.is_empty() {
+            return Ok(());
+        }
         self.start_color_marker(color)?;
+        self.written += 1;
         self.writer.write_str(str)?;
         self.end_color_marker()?;
         Ok(())
@@ -32,31 +54,84 @@ where
     }
 
     pub fn start_color_marker(&mut self, color: Color) -> fmt::Result {
-        if self.add_delimiter {
-            self.writer.write_char(' ')?;
-            self.add_delimiter = false;
+        self.write_delimiter()?;
+        // Text without color takes the style of the segment.
+        let color = match self.base_color {
+            Some(base_color) if color.is_plain() => base_color,
+            _ => color,
+        };
+        // Colored tags don't inherit the attributes of an already started
+        // segment style.
+        if self.base_color_started {
+            self.reset_color()?;
         }
         match self.shell {
-            Shell::Tmux => self.writer.write_str(color.tmux_start_code()),
+            Shell::Tmux => self.writer.write_str(&color.tmux_start_code()),
             Shell::None => Ok(()),
-            _ => self.apply_shell_markers(color.terminal_start_code()),
+            _ => self.apply_shell_markers(&color.terminal_start_code()),
         }
     }
 
     pub fn end_color_marker(&mut self) -> fmt::Result {
-        if self.add_delimiter {
-            self.writer.write_char(' ')?;
-            self.add_delimiter = false;
-        }
+        self.write_delimiter()?;
+        self.reset_color()
+    }
+
+    fn reset_color(&mut self) -> fmt::Result {
+        self.base_color_started = false;
         match self.shell {
-            Shell::Tmux => self.writer.write_str("#[fg=default]"),
+            Shell::Tmux => self.writer.write_str("#[default]"),
             Shell::None => Ok(()),
             _ => self.apply_shell_markers("\x1b[0;39m"),
         }
     }
 
+    /// Separates the next output by the spacing, unless nothing was written
+    /// yet.
     pub fn add_delimter(&mut self) {
-        self.add_delimiter = true;
+        let spacing = self.spacing.clone();
+        self.add_separator(&spacing);
+    }
+
+    /// Separates the next output by `separator`, unless nothing was written
+    /// yet.
+    pub fn add_separator(&mut self, separator: &str) {
+        if self.written > 0 {
+            self.delimiter = Some(separator.into());
+        }
+    }
+
+    /// Writes a segment of the prompt in the given style. Delimiters added at
+    /// the end of the segment are dropped, so that only the layout decides
+    /// about the spacing between segments.
+    pub fn segment<F>(&mut self, style: Option<Color>, add_segment: F) -> fmt::Result
+    where
+        F: FnOnce(&mut Self) -> fmt::Result,
+    {
+        let delimiter = self.delimiter.clone();
+        let written = self.written;
+        self.base_color = style;
+        self.base_color_started = false;
+
+        add_segment(self)?;
+
+        if self.base_color_started {
+            self.reset_color()?;
+        }
+        self.base_color = None;
+        self.delimiter = if self.written == written {
+            delimiter
+        } else {
+            None
+        };
+        Ok(())
+    }
+
+    fn write_delimiter(&mut self) -> fmt::Result {
+        if let Some(delimiter) = self.delimiter.take() {
+            self.writer.write_str(&delimiter)?;
+        }
+        Ok(())
     }
 
     fn apply_shell_markers(&mut self, marker: &str) -> fmt::Result {
@@ -73,10 +148,15 @@ where
     W: fmt::Write,
 {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        if self.add_delimiter {
-            self.writer.write_char(' ')?;
-            self.add_delimiter = false;
+        if s.is_empty() {
+            return Ok(());
+        }
+        self.write_delimiter()?;
+        if let (Some(color), false) = (self.base_color, self.base_color_started) {
+            self.start_color_marker(color)?;
+            self.base_color_started = true;
         }
+        self.written += 1;
         self.writer.write_str(s)
     }
 }
@@ -1,15 +1,24 @@
-use crate::{config::types::Config, git::types::GitRepoState};
-use std::fmt::{self, Write};
+use crate::{
+    config::types::{Config, Spacing, TagPosition},
+    git::{branch::build_fully_qualified_remote_branch_name, types::GitRepoState},
+};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+};
 
 use super::{
     output::TerminalOutput,
-    types::{ColoredTag, Shell},
+    template::{Placeholder, TemplateItem},
+    types::{Color, CountTag, Shell},
 };
 
 pub struct Prompt {
     shell: Shell,
     config: Config,
     repo_state: GitRepoState,
+    config_error: bool,
+    custom_segments: BTreeMap<String, String>,
 }
 
 impl Prompt {
@@ -18,9 +27,30 @@ impl Prompt {
             shell,
             config,
             repo_state,
+            config_error: false,
+            custom_segments: BTreeMap::new(),
         }
     }
 
+    /// Marks the prompt as rendered with the default config because the
+    /// configured one could not be loaded.
+    pub fn with_config_error(mut self, config_error: bool) -> Prompt {
+        self.config_error = config_error;
+        self
+    }
+
+    /// Sets the outputs of the custom segments by name.
+    pub fn with_custom_segments(mut self, custom_segments: BTreeMap<String, String>) -> Prompt {
+        self.custom_segments = custom_segments;
+        self
+    }
+
+    fn add_config_error_indicator<W: Write>(&self, output: &mut TerminalOutput<W>) -> fmt::Result {
+        output.colored_tag(&self.config.config_error_indicator)?;
+        output.add_separator(&self.config.spacing.separator);
+        Ok(())
+    }
+
     fn add_repo_indicator<W: Write>(&self, output: &mut TerminalOutput<W>) -> fmt::Result {
         output.write_str(&self.config.repo_indicator)?;
         output.add_delimter();
@@ -41,28 +71,51 @@ impl Prompt {
     }
 
     fn add_merge_branch_commits<W: Write>(&self, output: &mut TerminalOutput<W>) -> fmt::Result {
-        let push = self.repo_state.merge_branch_commits_to_push;
-        let pull = self.repo_state.merge_branch_commits_to_pull;
+        let push = self.count(
+            self.repo_state.merge_branch_commits_to_push,
+            self.repo_state.merge_branch_merges_to_push,
+        );
+        let pull = self.count(
+            self.repo_state.merge_branch_commits_to_pull,
+            self.repo_state.merge_branch_merges_to_pull,
+        );
 
-        if push > 0 && pull > 0 {
+        if self.repo_state.unknown.merge_branch_commits {
+            output.write_str(&self.config.merge_branch_commits_indicator)?;
+            output.add_delimter();
+            output.colored_tag(&self.config.unknown_indicator)?;
+            output.add_delimter();
+        } else if !push.is_empty() && !pull.is_empty() {
             output.write_str(&self.config.merge_branch_commits_indicator)?;
             output.add_delimter();
             write!(output, "{pull}")?;
-            output.colored_tag(&self.config.merge_branch_commits_both_pull_push)?;
+            output.colored_tag(
+                self.config
+                    .merge_branch_commits_both_pull_push
+                    .for_count(pull.total().max(push.total())),
+            )?;
             output.add_delimter();
             write!(output, "{push}")?;
             output.add_delimter();
-        } else if pull > 0 {
+        } else if !pull.is_empty() {
             output.write_str(&self.config.merge_branch_commits_indicator)?;
             output.add_delimter();
-            output.colored_tag(&self.config.merge_branch_commits_only_pull)?;
+            output.colored_tag(
+                self.config
+                    .merge_branch_commits_only_pull
+                    .for_count(pull.total()),
+            )?;
             output.add_delimter();
             write!(output, "{pull}")?;
             output.add_delimter();
-        } else if push > 0 {
+        } else if !push.is_empty() {
             output.write_str(&self.config.merge_branch_commits_indicator)?;
             output.add_delimter();
-            output.colored_tag(&self.config.merge_branch_commits_only_push)?;
+            output.colored_tag(
+                self.config
+                    .merge_branch_commits_only_push
+                    .for_count(push.total()),
+            )?;
             output.add_delimter();
             write!(output, "{push}")?;
             output.add_delimter();
@@ -102,133 +155,598 @@ impl Prompt {
     }
 
     fn add_local_commits<W: Write>(&self, output: &mut TerminalOutput<W>) -> fmt::Result {
-        let push = self.repo_state.commits_to_push;
-        let pull = self.repo_state.commits_to_pull;
+        let push = self.count(
+            self.repo_state.commits_to_push,
+            self.repo_state.merges_to_push,
+        );
+        let pull = self.count(
+            self.repo_state.commits_to_pull,
+            self.repo_state.merges_to_pull,
+        );
 
-        if push > 0 && pull > 0 {
+        if self.repo_state.unknown.commits_to_origin {
+            output.colored_tag(&self.config.unknown_indicator)?;
+            output.add_delimter();
+        } else if !push.is_empty() && !pull.is_empty() {
             write!(output, "{pull}")?;
-            output.colored_tag(&self.config.local_commits_push_pull_infix)?;
+            output.colored_tag(
+                self.config
+                    .local_commits_push_pull_infix
+                    .for_count(pull.total().max(push.total())),
+            )?;
             write!(output, "{push}")?;
             output.add_delimter();
-        } else if pull > 0 {
+        } else if !pull.is_empty() {
             write!(output, "{pull}")?;
-            output.colored_tag(&self.config.local_commits_pull_suffix)?;
+            output.colored_tag(
+                self.config
+                    .local_commits_pull_suffix
+                    .for_count(pull.total()),
+            )?;
             output.add_delimter();
-        } else if push > 0 {
+        } else if !push.is_empty() {
             write!(output, "{push}")?;
-            output.colored_tag(&self.config.local_commits_push_suffix)?;
+            output.colored_tag(
+                self.config
+                    .local_commits_push_suffix
+                    .for_count(push.total()),
+            )?;
             output.add_delimter();
         }
         Ok(())
     }
 
     fn add_repo_state<W: Write>(&self, output: &mut TerminalOutput<W>) -> fmt::Result {
+        if self.repo_state.unknown.local_repo_changes {
+            output.colored_tag(&self.config.unknown_indicator)?;
+            output.add_delimter();
+            return Ok(());
+        }
+
         add_state_elem(
             output,
+            &self.config.spacing,
             self.repo_state.git_local_repo_changes.index_add,
             &self.config.change_index_add_suffix,
         )?;
         add_state_elem(
             output,
+            &self.config.spacing,
             self.repo_state.git_local_repo_changes.index_del,
             &self.config.change_index_del_suffix,
         )?;
         add_state_elem(
             output,
+            &self.config.spacing,
             self.repo_state.git_local_repo_changes.index_mod,
             &self.config.change_index_mod_suffix,
         )?;
         add_state_elem(
             output,
+            &self.config.spacing,
             self.repo_state.git_local_repo_changes.renamed,
             &self.config.change_renamed_suffix,
         )?;
-        output.add_delimter();
+        output.add_separator(&self.config.spacing.change_groups);
 
         add_state_elem(
             output,
+            &self.config.spacing,
             self.repo_state.git_local_repo_changes.local_del,
             &self.config.change_local_del_suffix,
         )?;
         add_state_elem(
             output,
+            &self.config.spacing,
             self.repo_state.git_local_repo_changes.local_mod,
             &self.config.change_local_mod_suffix,
         )?;
-        output.add_delimter();
+        output.add_separator(&self.config.spacing.change_groups);
 
         add_state_elem(
             output,
+            &self.config.spacing,
             self.repo_state.git_local_repo_changes.local_add,
             &self.config.change_local_add_suffix,
         )?;
-        output.add_delimter();
+        output.add_separator(&self.config.spacing.change_groups);
 
         add_state_elem(
             output,
+            &self.config.spacing,
             self.repo_state.git_local_repo_changes.conflict,
             &self.config.change_conflicted_suffix,
         )?;
-        output.add_delimter();
+        output.add_separator(&self.config.spacing.change_groups);
 
         Ok(())
     }
 
     fn add_stashes<W: Write>(&self, output: &mut TerminalOutput<W>) -> fmt::Result {
+        if self.repo_state.unknown.stash_count {
+            return output.colored_tag(&self.config.unknown_indicator);
+        }
         add_state_elem(
             output,
+            &self.config.spacing,
             self.repo_state.stash_count,
             &self.config.stash_suffix,
         )?;
         Ok(())
     }
 
-    fn show_merge_branch_indicator(&self) -> bool {
-        self.config.parts.show_merge_branch_commits_diff
-            && !self
-                .config
-                .merge_branch_ignore_branches
-                .contains(&self.repo_state.local_branch)
-    }
-}
-
-impl fmt::Display for Prompt {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut output = TerminalOutput::new(self.shell, f);
+    fn add_placeholder<W: Write>(
+        &self,
+        output: &mut TerminalOutput<W>,
+        placeholder: &Placeholder,
+    ) -> fmt::Result {
+        let state = &self.repo_state;
+        let changes = &state.git_local_repo_changes;
 
-        output.end_color_marker()?;
-        if self.config.parts.show_repo_indicator {
-            self.add_repo_indicator(&mut output)?;
+        match placeholder {
+            Placeholder::RepoIndicator => self.add_repo_indicator(output),
+            Placeholder::MergeBranch => {
+                self.add_no_tracked_upstream_indicator(output)?;
+                self.add_merge_branch_commits(output)
+            }
+            Placeholder::Branch => self.add_local_branch_name(output),
+            Placeholder::LocalCommits => self.add_local_commits(output),
+            Placeholder::Changes => self.add_repo_state(output),
+            Placeholder::Stash => self.add_stashes(output),
+            Placeholder::LocalBranch => output.write_str(&state.local_branch),
+            Placeholder::CommitTag if state.unknown.commit_tag => {
+                output.colored_tag(&self.config.unknown_indicator)
+            }
+            Placeholder::CommitTag => output.write_str(&state.commit_tag),
+            Placeholder::CommitSha => output.write_str(&state.commit_short_sha),
+            Placeholder::RemoteBranch => {
+                if state.remote_tracking_branch.is_empty() {
+                    return Ok(());
+                }
+                output.write_str(&build_fully_qualified_remote_branch_name(
+                    &state.remote,
+                    &state.remote_tracking_branch,
+                ))
+            }
+            Placeholder::MergeBranchName => output.write_str(&state.merge_branch),
+            Placeholder::CommitsToPush => self.add_count(
+                output,
+                state.unknown.commits_to_origin,
+                self.count(state.commits_to_push, state.merges_to_push),
+            ),
+            Placeholder::CommitsToPull => self.add_count(
+                output,
+                state.unknown.commits_to_origin,
+                self.count(state.commits_to_pull, state.merges_to_pull),
+            ),
+            Placeholder::MergeBranchCommitsToPush => self.add_count(
+                output,
+                state.unknown.merge_branch_commits,
+                self.count(
+                    state.merge_branch_commits_to_push,
+                    state.merge_branch_merges_to_push,
+                ),
+            ),
+            Placeholder::MergeBranchCommitsToPull => self.add_count(
+                output,
+                state.unknown.merge_branch_commits,
+                self.count(
+                    state.merge_branch_commits_to_pull,
+                    state.merge_branch_merges_to_pull,
+                ),
+            ),
+            Placeholder::StashCount => self.add_count(
+                output,
+                state.unknown.stash_count,
+                self.count(state.stash_count, 0),
+            ),
+            Placeholder::IndexAdd => self.add_change_count(output, changes.index_add),
+            Placeholder::IndexMod => self.add_change_count(output, changes.index_mod),
+            Placeholder::IndexDel => self.add_change_count(output, changes.index_del),
+            Placeholder::LocalAdd => self.add_change_count(output, changes.local_add),
+            Placeholder::LocalMod => self.add_change_count(output, changes.local_mod),
+            Placeholder::LocalDel => self.add_change_count(output, changes.local_del),
+            Placeholder::Renamed => self.add_change_count(output, changes.renamed),
+            Placeholder::Conflicted => self.add_change_count(output, changes.conflict),
+            Placeholder::Custom(name) => self.add_custom_segment(output, name),
         }
-        if self.show_merge_branch_indicator() {
-            self.add_no_tracked_upstream_indicator(&mut output)?;
-            self.add_merge_branch_commits(&mut output)?;
+    }
+
+    fn add_custom_segment<W: Write>(
+        &self,
+        output: &mut TerminalOutput<W>,
+        name: &str,
+    ) -> fmt::Result {
+        let (Some(segment), Some(text)) =
+            (self.config.custom.get(name), self.custom_segments.get(name))
+        else {
+            return Ok(());
+        };
+        if text.is_empty() {
+            return Ok(());
         }
-        if self.config.parts.show_local_branch {
-            self.add_local_branch_name(&mut output)?;
+        output.string_in_color(segment.tag.color, &format!("{}{text}", segment.tag.tag))
+    }
+
+    /// A single count, nothing for zero.
+    fn add_count<W: Write>(
+        &self,
+        output: &mut TerminalOutput<W>,
+        unknown: bool,
+        count: Count,
+    ) -> fmt::Result {
+        if unknown {
+            output.colored_tag(&self.config.unknown_indicator)
+        } else if !count.is_empty() {
+            write!(output, "{count}")
+        } else {
+            Ok(())
         }
-        if self.config.parts.show_commits_to_origin {
-            self.add_local_commits(&mut output)?;
+    }
+
+    fn add_change_count<W: Write>(
+        &self,
+        output: &mut TerminalOutput<W>,
+        count: usize,
+    ) -> fmt::Result {
+        self.add_count(
+            output,
+            self.repo_state.unknown.local_repo_changes,
+            Count {
+                value: count,
+                merges: 0,
+                limit: 0,
+            },
+        )
+    }
+
+    fn count(&self, value: usize, merges: usize) -> Count {
+        Count {
+            value,
+            merges,
+            limit: self.config.commit_count_limit,
         }
-        if self.config.parts.show_local_changes_state {
-            self.add_repo_state(&mut output)?;
+    }
+
+    fn add_items<W: Write>(
+        &self,
+        output: &mut TerminalOutput<W>,
+        items: &[TemplateItem],
+    ) -> fmt::Result {
+        for item in items {
+            match item {
+                TemplateItem::Text(text) => output.write_str(text)?,
+                TemplateItem::Space => output.add_separator(&self.config.spacing.separator),
+                TemplateItem::Placeholder { placeholder, style } => {
+                    if self.is_shown(placeholder) {
+                        output.segment(self.style(placeholder, *style), |output| {
+                            self.add_placeholder(output, placeholder)
+                        })?;
+                    }
+                }
+                TemplateItem::Group(items) => {
+                    if !self.has_empty_placeholder(items) {
+                        self.add_items(output, items)?;
+                    }
+                }
+            }
         }
-        if self.config.parts.show_stashes {
-            self.add_stashes(&mut output)?;
+        Ok(())
+    }
+
+    fn has_empty_placeholder(&self, items: &[TemplateItem]) -> bool {
+        items.iter().any(|item| match item {
+            TemplateItem::Placeholder { placeholder, .. } => {
+                !self.is_shown(placeholder) || self.is_empty(placeholder)
+            }
+            _ => false,
+        })
+    }
+
+    /// Whether the placeholder renders to nothing.
+    fn is_empty(&self, placeholder: &Placeholder) -> bool {
+        let mut rendered = String::new();
+        let mut output = TerminalOutput::new(Shell::None, &mut rendered);
+        self.add_placeholder(&mut output, placeholder).is_err() || rendered.is_empty()
+    }
+
+    fn is_shown(&self, placeholder: &Placeholder) -> bool {
+        self.config
+            .is_shown(placeholder, &self.repo_state.local_branch)
+    }
+
+    /// The style of the last matching branch rule, or the one of the format.
+    fn style(&self, placeholder: &Placeholder, style: Option<Color>) -> Option<Color> {
+        self.config
+            .matching_branch_rules(&self.repo_state.local_branch)
+            .filter_map(|rule| rule.style.get(placeholder))
+            .last()
+            .map(|style| style.color)
+            .or(style)
+    }
+}
+
+/// A commit count, with merge commits (only counted separately when
+/// `merge_commits = "both"`) rendered in parentheses, e.g. `3(1)`.
+struct Count {
+    value: usize,
+    merges: usize,
+    limit: usize,
+}
+
+impl Count {
+    fn is_empty(&self) -> bool {
+        self.total() == 0
+    }
+
+    fn total(&self) -> usize {
+        self.value + self.merges
+    }
+
+    fn fmt_capped(&self, f: &mut fmt::Formatter<'_>, value: usize) -> fmt::Result {
+        if self.limit > 0 && value > self.limit {
+            write!(f, "{}+", self.limit)
+        } else {
+            write!(f, "{value}")
         }
+    }
+}
 
+impl fmt::Display for Count {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_capped(f, self.value)?;
+        if self.merges > 0 {
+            f.write_str("(")?;
+            self.fmt_capped(f, self.merges)?;
+            f.write_str(")")?;
+        }
         Ok(())
     }
 }
 
+impl fmt::Display for Prompt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut output =
+            TerminalOutput::new(self.shell, f).with_spacing(&self.config.spacing.inner);
+
+        output.end_color_marker()?;
+        if self.config_error {
+            self.add_config_error_indicator(&mut output)?;
+        }
+        self.add_items(&mut output, &self.config.format.items)
+    }
+}
+
 fn add_state_elem<W: Write>(
     output: &mut TerminalOutput<W>,
+    spacing: &Spacing,
     state_elem: usize,
-    colored_tag: &ColoredTag,
+    count_tag: &CountTag,
 ) -> fmt::Result {
-    if state_elem > 0 {
-        write!(output, "{state_elem}")?;
-        output.colored_tag(colored_tag)?;
+    if state_elem == 0 {
+        return Ok(());
+    }
+    let tag = count_tag.for_count(state_elem);
+    match spacing.tag_position {
+        TagPosition::After => {
+            write!(output, "{state_elem}")?;
+            output.add_separator(&spacing.count_tag);
+            output.colored_tag(tag)?;
+        }
+        TagPosition::Before => {
+            output.colored_tag(tag)?;
+            output.add_separator(&spacing.count_tag);
+            write!(output, "{state_elem}")?;
+        }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::git::{
+        fake::FakeBackend,
+        get_git_repo_state,
+        types::{GitLocalRepoChanges, GitUnknownState},
+    };
+
+    use super::*;
+
+    fn repo_state() -> GitRepoState {
+        GitRepoState {
+            local_branch: "feature".into(),
+            commit_short_sha: "1234567".into(),
+            remote: "origin".into(),
+            remote_tracking_branch: "refs/heads/feature".into(),
+            commits_to_push: 2,
+            commits_to_pull: 1,
+            merge_branch_commits_to_pull: 3,
+            stash_count: 1,
+            git_local_repo_changes: GitLocalRepoChanges {
+                index_add: 1,
+                local_mod: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn config(format: &str) -> Config {
+        Config {
+            format: format.to_string().try_into().unwrap(),
+            ..Default::default()
+        }
+    }
+
+    /// Queries the fake repository like a real one and renders the prompt.
+    fn render(config: Config, repo_state: GitRepoState) -> String {
+        let queries = config.git_queries(&repo_state.local_branch);
+        let backend = FakeBackend::new(repo_state);
+        let repo_state = get_git_repo_state(Box::new(backend), queries, None).unwrap();
+
+        Prompt::new(Shell::None, config, repo_state).to_string()
+    }
+
+    #[test]
+    fn renders_the_default_prompt() {
+        assert_eq!(
+            render(Config::default(), repo_state()),
+            "ᚴ 𝘮 → 3 [feature] 1⥯2 1A 2M 1≡"
+        );
+    }
+
+    #[test]
+    fn renders_a_missing_upstream() {
+        let repo_state = GitRepoState {
+            remote: "".into(),
+            remote_tracking_branch: "".into(),
+            ..repo_state()
+        };
+
+        assert_eq!(
+            render(Config::default(), repo_state),
+            "ᚴ upstream ⚡ [feature] 1A 2M 1≡"
+        );
+    }
+
+    #[test]
+    fn renders_a_detached_head() {
+        let repo_state = GitRepoState {
+            local_branch: "".into(),
+            ..repo_state()
+        };
+
+        assert_eq!(
+            render(config("{branch}"), repo_state.clone()),
+            "[detached@1234567]"
+        );
+        assert_eq!(
+            render(
+                config("{branch}"),
+                GitRepoState {
+                    commit_tag: "v1.0".into(),
+                    ..repo_state
+                }
+            ),
+            "[detached@v1.0]"
+        );
+    }
+
+    #[test]
+    fn renders_single_values() {
+        assert_eq!(
+            render(
+                config("{local_branch} {remote_branch} {merge_branch_name} {commits_to_push}"),
+                repo_state()
+            ),
+            "feature origin/feature origin/master 2"
+        );
+    }
+
+    #[test]
+    fn renders_unknown_values() {
+        let mut repo_state = repo_state();
+        repo_state.unknown = GitUnknownState {
+            stash_count: true,
+            commit_tag: true,
+            local_repo_changes: true,
+            commits_to_origin: true,
+            merge_branch_commits: true,
+        };
+        let config = config("{merge_branch} {local_commits} {changes} {stash} {commit_tag}");
+
+        assert_eq!(
+            Prompt::new(Shell::None, config, repo_state).to_string(),
+            "𝘮 ? ? ? ? ?"
+        );
+    }
+
+    #[test]
+    fn ignored_branches_hide_the_merge_branch() {
+        let mut config =
+            config("{merge_branch} {merge_branch_name} {merge_branch_commits_to_pull} {branch}");
+        config.merge_branch_ignore_branches = vec!["feat*".to_string().try_into().unwrap()];
+
+        assert_eq!(render(config, repo_state()), "[feature]");
+    }
+
+    #[test]
+    fn groups_with_an_empty_placeholder_are_omitted() {
+        assert_eq!(
+            render(
+                config("{branch}{?({stash_count})}{?({index_del})}{?<{index_add}{?{renamed}}>}"),
+                repo_state()
+            ),
+            "[feature](1)<1>"
+        );
+    }
+
+    #[test]
+    fn has_empty_placeholder() {
+        let format = "{?{stash_count}{index_del}}";
+        let template = config(format).format;
+        let TemplateItem::Group(items) = &template.items[0] else {
+            panic!("no group");
+        };
+        let prompt = |repo_state| Prompt::new(Shell::None, config(format), repo_state);
+
+        assert!(prompt(repo_state()).has_empty_placeholder(items));
+
+        let mut repo_state = repo_state();
+        repo_state.git_local_repo_changes.index_del = 1;
+        assert!(!prompt(repo_state.clone()).has_empty_placeholder(items));
+
+        repo_state.stash_count = 0;
+        assert!(prompt(repo_state).has_empty_placeholder(items));
+    }
+
+    #[test]
+    fn renders_the_config_error_indicator() {
+        let prompt = Prompt::new(Shell::None, config("{branch}"), repo_state());
+
+        assert_eq!(prompt.with_config_error(true).to_string(), "! [feature]");
+    }
+
+    #[test]
+    fn colored_tags_reset_the_segment_style() {
+        let prompt = Prompt::new(Shell::Other, config("{stash:bold}"), repo_state());
+
+        assert_eq!(
+            prompt.to_string(),
+            "\x1b[0;39m\x1b[0;1;39m1\x1b[0;39m\x1b[1;32m≡\x1b[0;39m"
+        );
+    }
+
+    #[test]
+    fn formats_counts() {
+        let count = |value, merges, limit| {
+            Count {
+                value,
+                merges,
+                limit,
+            }
+            .to_string()
+        };
+
+        assert_eq!(count(3, 0, 0), "3");
+        assert_eq!(count(3, 1, 0), "3(1)");
+        assert_eq!(count(0, 2, 0), "0(2)");
+        assert_eq!(count(5, 0, 5), "5");
+        assert_eq!(count(6, 0, 5), "5+");
+        assert_eq!(count(6, 2, 5), "5+(2)");
+        assert_eq!(count(9, 7, 5), "5+(5+)");
+    }
+
+    #[test]
+    fn counts_are_capped_by_the_limit() {
+        let mut config = config("{commits_to_push} {commits_to_pull}");
+        config.commit_count_limit = 5;
+        let repo_state = GitRepoState {
+            commits_to_push: 6,
+            commits_to_pull: 5,
+            ..repo_state()
+        };
+
+        assert_eq!(render(config, repo_state), "5+ 5");
+    }
+}
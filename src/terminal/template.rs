@@ -0,0 +1,499 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{is_not, tag, take_while1},
+    character::complete::{char, multispace1},
+    combinator::{map, opt},
+    multi::many0,
+    sequence::{delimited, pair, preceded},
+    IResult,
+};
+use serde::{de::IntoDeserializer, Deserialize, Serialize};
+use std::fmt;
+
+use crate::{config::types::Parts, git::types::GitQueries};
+
+use super::types::{BaseColor, Color, ColorIntensity, RgbColor, TermColor};
+
+/// A prompt layout like `{repo_indicator} {branch} {local_commits}`, parsed
+/// when the configuration is loaded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Template {
+    source: String,
+    pub items: Vec<TemplateItem>,
+}
+
+#[derive(Debug, Clone)]
+pub enum TemplateItem {
+    Text(String),
+    /// A run of whitespace, shown as a single space between non-empty output.
+    Space,
+    Placeholder {
+        placeholder: Placeholder,
+        style: Option<Color>,
+    },
+    /// `{?...}`, omitted if any of its placeholders (outside of nested groups)
+    /// is empty.
+    Group(Vec<TemplateItem>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Placeholder {
+    RepoIndicator,
+    MergeBranch,
+    Branch,
+    LocalCommits,
+    Changes,
+    Stash,
+    LocalBranch,
+    CommitTag,
+    CommitSha,
+    RemoteBranch,
+    MergeBranchName,
+    CommitsToPush,
+    CommitsToPull,
+    MergeBranchCommitsToPush,
+    MergeBranchCommitsToPull,
+    StashCount,
+    IndexAdd,
+    IndexMod,
+    IndexDel,
+    LocalAdd,
+    LocalMod,
+    LocalDel,
+    Renamed,
+    Conflicted,
+    /// `{custom.<name>}`, the output of a custom segment.
+    Custom(String),
+}
+
+const PLACEHOLDERS: &[(&str, Placeholder)] = &[
+    ("repo_indicator", Placeholder::RepoIndicator),
+    ("merge_branch", Placeholder::MergeBranch),
+    ("branch", Placeholder::Branch),
+    ("local_commits", Placeholder::LocalCommits),
+    ("changes", Placeholder::Changes),
+    ("stash", Placeholder::Stash),
+    ("local_branch", Placeholder::LocalBranch),
+    ("commit_tag", Placeholder::CommitTag),
+    ("commit_sha", Placeholder::CommitSha),
+    ("remote_branch", Placeholder::RemoteBranch),
+    ("merge_branch_name", Placeholder::MergeBranchName),
+    ("commits_to_push", Placeholder::CommitsToPush),
+    ("commits_to_pull", Placeholder::CommitsToPull),
+    (
+        "merge_branch_commits_to_push",
+        Placeholder::MergeBranchCommitsToPush,
+    ),
+    (
+        "merge_branch_commits_to_pull",
+        Placeholder::MergeBranchCommitsToPull,
+    ),
+    ("stash_count", Placeholder::StashCount),
+    ("index_add", Placeholder::IndexAdd),
+    ("index_mod", Placeholder::IndexMod),
+    ("index_del", Placeholder::IndexDel),
+    ("local_add", Placeholder::LocalAdd),
+    ("local_mod", Placeholder::LocalMod),
+    ("local_del", Placeholder::LocalDel),
+    ("renamed", Placeholder::Renamed),
+    ("conflicted", Placeholder::Conflicted),
+];
+
+impl Placeholder {
+    fn from_name(name: &str) -> Option<Placeholder> {
+        if let Some(custom) = name.strip_prefix("custom.") {
+            return (!custom.is_empty()).then(|| Placeholder::Custom(custom.into()));
+        }
+        PLACEHOLDERS
+            .iter()
+            .find(|(placeholder_name, _)| *placeholder_name == name)
+            .map(|(_, placeholder)| placeholder.clone())
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Placeholder::Custom(name) => format!("custom.{name}"),
+            placeholder => PLACEHOLDERS
+                .iter()
+                .find(|(_, known)| known == placeholder)
+                .map(|(name, _)| name.to_string())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Whether the segment is enabled in `parts`, single values always are.
+    pub fn is_shown(&self, parts: &Parts) -> bool {
+        match self {
+            Placeholder::RepoIndicator => parts.show_repo_indicator,
+            Placeholder::MergeBranch => parts.show_merge_branch_commits_diff,
+            Placeholder::Branch => parts.show_local_branch,
+            Placeholder::LocalCommits => parts.show_commits_to_origin,
+            Placeholder::Changes => parts.show_local_changes_state,
+            Placeholder::Stash => parts.show_stashes,
+            _ => true,
+        }
+    }
+
+    /// Whether the placeholder shows the merge branch or its commit counts.
+    pub fn is_merge_branch(&self) -> bool {
+        matches!(
+            self,
+            Placeholder::MergeBranch
+                | Placeholder::MergeBranchName
+                | Placeholder::MergeBranchCommitsToPush
+                | Placeholder::MergeBranchCommitsToPull
+        )
+    }
+
+    fn add_queries(&self, queries: &mut GitQueries, detached: bool) {
+        match self {
+            // The tag is only shown in place of a detached branch.
+            Placeholder::Branch => queries.commit_tag |= detached,
+            Placeholder::CommitTag => queries.commit_tag = true,
+            Placeholder::MergeBranch
+            | Placeholder::MergeBranchName
+            | Placeholder::MergeBranchCommitsToPush
+            | Placeholder::MergeBranchCommitsToPull => queries.merge_branch_commits = true,
+            Placeholder::LocalCommits | Placeholder::CommitsToPush | Placeholder::CommitsToPull => {
+                queries.commits_to_origin = true
+            }
+            Placeholder::Changes
+            | Placeholder::IndexAdd
+            | Placeholder::IndexMod
+            | Placeholder::IndexDel
+            | Placeholder::LocalAdd
+            | Placeholder::LocalMod
+            | Placeholder::LocalDel
+            | Placeholder::Renamed
+            | Placeholder::Conflicted => queries.local_repo_changes = true,
+            Placeholder::Stash | Placeholder::StashCount => queries.stash_count = true,
+            Placeholder::RepoIndicator
+            | Placeholder::LocalBranch
+            | Placeholder::CommitSha
+            | Placeholder::RemoteBranch
+            | Placeholder::Custom(_) => (),
+        }
+    }
+}
+
+impl Template {
+    /// The git queries needed for the shown placeholders.
+    pub fn git_queries(
+        &self,
+        is_shown: impl Fn(&Placeholder) -> bool,
+        detached: bool,
+    ) -> GitQueries {
+        let mut queries = GitQueries::default();
+        for placeholder in self.placeholders() {
+            if is_shown(placeholder) {
+                placeholder.add_queries(&mut queries, detached);
+            }
+        }
+        queries
+    }
+
+    /// The names of the custom segments used in the template.
+    pub fn custom_segments(&self) -> Vec<&str> {
+        self.placeholders()
+            .into_iter()
+            .filter_map(|placeholder| match placeholder {
+                Placeholder::Custom(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn placeholders(&self) -> Vec<&Placeholder> {
+        let mut placeholders = Vec::new();
+        add_placeholders(&self.items, &mut placeholders);
+        placeholders
+    }
+}
+
+fn add_placeholders<'a>(items: &'a [TemplateItem], placeholders: &mut Vec<&'a Placeholder>) {
+    for item in items {
+        match item {
+            TemplateItem::Placeholder { placeholder, .. } => placeholders.push(placeholder),
+            TemplateItem::Group(items) => add_placeholders(items, placeholders),
+            _ => (),
+        }
+    }
+}
+
+impl TryFrom<String> for Template {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        let (rest, raw_items) = many0(raw_item)(source.as_str()).map_err(|err| err.to_string())?;
+        if let Some(unexpected) = rest.chars().next() {
+            return Err(format!(
+                "unmatched `{unexpected}` at position {} (use `{unexpected}{unexpected}` for a literal `{unexpected}`)",
+                source.len() - rest.len() + 1
+            ));
+        }
+
+        let items = template_items(raw_items)?;
+        Ok(Template { source, items })
+    }
+}
+
+fn template_items(raw_items: Vec<RawItem<'_>>) -> Result<Vec<TemplateItem>, String> {
+    let mut items: Vec<TemplateItem> = Vec::new();
+    for raw_item in raw_items {
+        let item = match raw_item {
+            RawItem::Text(text) => match items.last_mut() {
+                Some(TemplateItem::Text(previous)) => {
+                    previous.push_str(text);
+                    continue;
+                }
+                _ => TemplateItem::Text(text.into()),
+            },
+            RawItem::Space => TemplateItem::Space,
+            RawItem::Placeholder(name, style) => TemplateItem::Placeholder {
+                placeholder: Placeholder::from_name(name)
+                    .ok_or_else(|| format!("unknown placeholder `{{{name}}}`"))?,
+                style: style.map(parse_style).transpose()?,
+            },
+            RawItem::Group(raw_items) => TemplateItem::Group(template_items(raw_items)?),
+        };
+        items.push(item);
+    }
+    Ok(items)
+}
+
+impl TryFrom<String> for Placeholder {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        Placeholder::from_name(&name).ok_or_else(|| format!("unknown placeholder `{name}`"))
+    }
+}
+
+impl From<Placeholder> for String {
+    fn from(placeholder: Placeholder) -> Self {
+        placeholder.name()
+    }
+}
+
+/// A style like `red bold`, see `parse_style`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Style {
+    source: String,
+    pub color: Color,
+}
+
+impl TryFrom<String> for Style {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        let color = parse_style(&source)?;
+        Ok(Style { source, color })
+    }
+}
+
+impl From<Style> for String {
+    fn from(style: Style) -> Self {
+        style.source
+    }
+}
+
+impl From<Template> for String {
+    fn from(template: Template) -> Self {
+        template.source
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+enum RawItem<'a> {
+    Text(&'a str),
+    Space,
+    Placeholder(&'a str, Option<&'a str>),
+    Group(Vec<RawItem<'a>>),
+}
+
+fn raw_item(input: &str) -> IResult<&str, RawItem<'_>> {
+    alt((map(tag("}}"), |_| RawItem::Text("}")), group_item))(input)
+}
+
+/// Inside of a group `}` always closes the group, there is no literal `}`.
+fn group_item(input: &str) -> IResult<&str, RawItem<'_>> {
+    alt((
+        map(tag("{{"), |_| RawItem::Text("{")),
+        group,
+        placeholder,
+        map(multispace1, |_| RawItem::Space),
+        map(is_not("{} \t\r\n"), RawItem::Text),
+    ))(input)
+}
+
+fn group(input: &str) -> IResult<&str, RawItem<'_>> {
+    map(
+        delimited(tag("{?"), many0(group_item), char('}')),
+        RawItem::Group,
+    )(input)
+}
+
+fn placeholder(input: &str) -> IResult<&str, RawItem<'_>> {
+    map(
+        delimited(
+            char('{'),
+            pair(
+                take_while1(|c: char| c.is_ascii_alphanumeric() || "_-.".contains(c)),
+                opt(preceded(char(':'), is_not("{}"))),
+            ),
+            char('}'),
+        ),
+        |(name, style)| RawItem::Placeholder(name, style),
+    )(input)
+}
+
+/// Parses a style like `red bold` or `208 bg=#303030 italic`.
+fn parse_style(style: &str) -> Result<Color, String> {
+    let mut color = Color::new(BaseColor::NoColor, ColorIntensity::Dull);
+
+    for word in style.split_whitespace() {
+        match word {
+            "bold" => color.bold = true,
+            "dim" => color.dim = true,
+            "italic" => color.italic = true,
+            "underline" => color.underline = true,
+            "vivid" => color.intensity = ColorIntensity::Vivid,
+            "dull" => color.intensity = ColorIntensity::Dull,
+            word => match word.strip_prefix("bg=") {
+                Some(background) => color.background = parse_color(background)?,
+                None => color.color = parse_color(word)?,
+            },
+        }
+    }
+
+    Ok(color)
+}
+
+fn parse_color(color: &str) -> Result<TermColor, String> {
+    if let Ok(index) = color.parse() {
+        return Ok(TermColor::Indexed(index));
+    }
+    if color.starts_with('#') {
+        return RgbColor::try_from(color.to_string()).map(TermColor::Rgb);
+    }
+    BaseColor::deserialize(IntoDeserializer::<serde::de::value::Error>::into_deserializer(color))
+        .map(TermColor::Named)
+        .map_err(|_| format!("invalid style `{color}`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<Template, String> {
+        Template::try_from(source.to_string())
+    }
+
+    /// The items in a compact notation: `<name>` for placeholders, `_` for
+    /// spaces and `(...)` for groups.
+    fn describe(items: &[TemplateItem]) -> String {
+        items
+            .iter()
+            .map(|item| match item {
+                TemplateItem::Text(text) => text.clone(),
+                TemplateItem::Space => "_".into(),
+                TemplateItem::Placeholder { placeholder, .. } => {
+                    format!("<{}>", placeholder.name())
+                }
+                TemplateItem::Group(items) => format!("({})", describe(items)),
+            })
+            .collect()
+    }
+
+    fn items(source: &str) -> String {
+        describe(&parse(source).unwrap().items)
+    }
+
+    #[test]
+    fn parses_placeholders_text_and_spaces() {
+        assert_eq!(
+            items("[{branch}]  {custom.k8s}\t{stash_count}"),
+            "[<branch>]_<custom.k8s>_<stash_count>"
+        );
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        assert_eq!(items("{{{branch}}}"), "{<branch>}");
+    }
+
+    #[test]
+    fn parses_groups() {
+        assert_eq!(
+            items("{?[{changes}]} {?{commits_to_pull}⇅{?{{{commits_to_push}}}"),
+            "([<changes>])_(<commits_to_pull>⇅({<commits_to_push>))"
+        );
+    }
+
+    #[test]
+    fn parses_styles() {
+        let template = parse("{branch:208 bg=#303030 bold}").unwrap();
+        let TemplateItem::Placeholder {
+            style: Some(style), ..
+        } = &template.items[0]
+        else {
+            panic!("no styled placeholder");
+        };
+
+        assert!(matches!(style.color, TermColor::Indexed(208)));
+        assert!(matches!(
+            style.background,
+            TermColor::Rgb(RgbColor(48, 48, 48))
+        ));
+        assert!(style.bold);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert_eq!(parse("{nope}").unwrap_err(), "unknown placeholder `{nope}`");
+        assert_eq!(
+            parse("{custom.}").unwrap_err(),
+            "unknown placeholder `{custom.}`"
+        );
+        assert_eq!(
+            parse("[{branch").unwrap_err(),
+            "unmatched `{` at position 2 (use `{{` for a literal `{`)"
+        );
+        assert_eq!(
+            parse("{branch} }").unwrap_err(),
+            "unmatched `}` at position 10 (use `}}` for a literal `}`)"
+        );
+        assert_eq!(parse("{branch:nope}").unwrap_err(), "invalid style `nope`");
+    }
+
+    #[test]
+    fn queries_only_shown_placeholders() {
+        let template = parse("{branch} {stash}").unwrap();
+        let mut parts = Parts::default();
+        let queries = |parts: &Parts, detached| {
+            template.git_queries(|placeholder| placeholder.is_shown(parts), detached)
+        };
+        assert!(queries(&parts, false).stash_count);
+
+        parts.show_stashes = false;
+        assert!(!queries(&parts, false).stash_count);
+        assert!(!queries(&parts, false).commit_tag);
+        assert!(queries(&parts, true).commit_tag);
+        assert!(!queries(&parts, true).local_repo_changes);
+    }
+
+    #[test]
+    fn round_trips_the_source() {
+        let source = "{?[{changes}]} {branch:red}";
+
+        assert_eq!(String::from(parse(source).unwrap()), source);
+    }
+}
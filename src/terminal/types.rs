@@ -1,7 +1,10 @@
 use clap::ValueEnum;
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
 use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, fmt};
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+/// One of the eight terminal colors or the default color.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
 pub enum BaseColor {
     Black,
@@ -15,76 +18,274 @@ pub enum BaseColor {
     NoColor,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+/// Bold / bright or normal text.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
 pub enum ColorIntensity {
     Dull,
     Vivid,
 }
 
+/// A named color, a 256-color index or a `#rrggbb` truecolor.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy)]
+#[serde(untagged, expecting = "a color name, a 256-color index or \"#rrggbb\"")]
+pub enum TermColor {
+    Named(BaseColor),
+    Indexed(u8),
+    Rgb(RgbColor),
+}
+
+impl From<BaseColor> for TermColor {
+    fn from(color: BaseColor) -> Self {
+        TermColor::Named(color)
+    }
+}
+
+impl TermColor {
+    /// SGR parameters of the color, `base` is 30 for the foreground and 40
+    /// for the background.
+    fn sgr_parameters(self, base: u8) -> Option<String> {
+        match self {
+            TermColor::Named(BaseColor::NoColor) => None,
+            TermColor::Named(color) => Some((base + color as u8).to_string()),
+            TermColor::Indexed(index) => Some(format!("{};5;{index}", base + 8)),
+            TermColor::Rgb(RgbColor(r, g, b)) => Some(format!("{};2;{r};{g};{b}", base + 8)),
+        }
+    }
+
+    fn tmux_name(self, intensity: ColorIntensity) -> Option<String> {
+        match (self, intensity) {
+            (TermColor::Named(BaseColor::NoColor), _) => None,
+            (TermColor::Named(color), ColorIntensity::Vivid) => {
+                Some(format!("bright{}", color.name()))
+            }
+            (TermColor::Named(color), ColorIntensity::Dull) => Some(color.name().into()),
+            (TermColor::Indexed(index), _) => Some(format!("colour{index}")),
+            (TermColor::Rgb(color), _) => Some(color.to_string()),
+        }
+    }
+}
+
+impl BaseColor {
+    fn name(self) -> &'static str {
+        match self {
+            BaseColor::Black => "black",
+            BaseColor::Red => "red",
+            BaseColor::Green => "green",
+            BaseColor::Yellow => "yellow",
+            BaseColor::Blue => "blue",
+            BaseColor::Magenta => "magenta",
+            BaseColor::Cyan => "cyan",
+            BaseColor::White => "white",
+            BaseColor::NoColor => "default",
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(try_from = "String", into = "String")]
+pub struct RgbColor(pub u8, pub u8, pub u8);
+
+impl TryFrom<String> for RgbColor {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("invalid color `{value}`, expected \"#rrggbb\"");
+        let hex = value
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6 && hex.is_ascii())
+            .ok_or_else(invalid)?;
+        let channel = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16);
+
+        match (channel(0), channel(2), channel(4)) {
+            (Ok(r), Ok(g), Ok(b)) => Ok(RgbColor(r, g, b)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl From<RgbColor> for String {
+    fn from(color: RgbColor) -> Self {
+        color.to_string()
+    }
+}
+
+impl fmt::Display for RgbColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+impl JsonSchema for RgbColor {
+    fn schema_name() -> Cow<'static, str> {
+        "RgbColor".into()
+    }
+
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "A truecolor as `#rrggbb`.",
+            "type": "string",
+            "pattern": "^#[0-9a-fA-F]{6}$"
+        })
+    }
+}
+
+/// A terminal color and text attributes.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
 pub struct Color {
-    pub color: BaseColor,
+    /// The text color: a name (`black`, `red`, `green`, `yellow`, `blue`,
+    /// `magenta`, `cyan`, `white` or `nocolor` for the default color), a
+    /// 256-color index, `#rrggbb` or the name of a `palette` color.
+    pub color: TermColor,
+    /// `vivid` (bold, bright named colors in tmux) or `dull`.
     pub intensity: ColorIntensity,
+    /// The background color, same values as `color`.
+    pub background: TermColor,
+    /// Bold text (independent of `intensity`).
+    pub bold: bool,
+    /// Dim text.
+    pub dim: bool,
+    /// Italic text.
+    pub italic: bool,
+    /// Underlined text.
+    pub underline: bool,
 }
 
 impl Color {
-    pub fn terminal_start_code(&self) -> &'static str {
-        match (self.color, self.intensity) {
-            (BaseColor::Black, ColorIntensity::Vivid) => "\x1b[1;30m",
-            (BaseColor::Red, ColorIntensity::Vivid) => "\x1b[1;31m",
-            (BaseColor::Green, ColorIntensity::Vivid) => "\x1b[1;32m",
-            (BaseColor::Yellow, ColorIntensity::Vivid) => "\x1b[1;33m",
-            (BaseColor::Blue, ColorIntensity::Vivid) => "\x1b[1;34m",
-            (BaseColor::Magenta, ColorIntensity::Vivid) => "\x1b[1;35m",
-            (BaseColor::Cyan, ColorIntensity::Vivid) => "\x1b[1;36m",
-            (BaseColor::White, ColorIntensity::Vivid) => "\x1b[1;37m",
-            (BaseColor::Black, ColorIntensity::Dull) => "\x1b[30m",
-            (BaseColor::Red, ColorIntensity::Dull) => "\x1b[31m",
-            (BaseColor::Green, ColorIntensity::Dull) => "\x1b[32m",
-            (BaseColor::Yellow, ColorIntensity::Dull) => "\x1b[33m",
-            (BaseColor::Blue, ColorIntensity::Dull) => "\x1b[34m",
-            (BaseColor::Magenta, ColorIntensity::Dull) => "\x1b[35m",
-            (BaseColor::Cyan, ColorIntensity::Dull) => "\x1b[36m",
-            (BaseColor::White, ColorIntensity::Dull) => "\x1b[37m",
-            (BaseColor::NoColor, _) => "\x1b[0;39m",
+    pub fn new(color: BaseColor, intensity: ColorIntensity) -> Self {
+        Self {
+            color: color.into(),
+            intensity,
+            background: BaseColor::NoColor.into(),
+            bold: false,
+            dim: false,
+            italic: false,
+            underline: false,
         }
     }
 
-    pub fn tmux_start_code(&self) -> &'static str {
-        match (self.color, self.intensity) {
-            (BaseColor::Black, ColorIntensity::Vivid) => "#[fg=brightblack]",
-            (BaseColor::Red, ColorIntensity::Vivid) => "#[fg=brightred]",
-            (BaseColor::Green, ColorIntensity::Vivid) => "#[fg=brightgreen]",
-            (BaseColor::Yellow, ColorIntensity::Vivid) => "#[fg=brightyellow]",
-            (BaseColor::Blue, ColorIntensity::Vivid) => "#[fg=brightblue]",
-            (BaseColor::Magenta, ColorIntensity::Vivid) => "#[fg=brightmagenta]",
-            (BaseColor::Cyan, ColorIntensity::Vivid) => "#[fg=brightcyan]",
-            (BaseColor::White, ColorIntensity::Vivid) => "#[fg=brightwhite]",
-            (BaseColor::Black, ColorIntensity::Dull) => "#[fg=black]",
-            (BaseColor::Red, ColorIntensity::Dull) => "#[fg=red]",
-            (BaseColor::Green, ColorIntensity::Dull) => "#[fg=green]",
-            (BaseColor::Yellow, ColorIntensity::Dull) => "#[fg=yellow]",
-            (BaseColor::Blue, ColorIntensity::Dull) => "#[fg=blue]",
-            (BaseColor::Magenta, ColorIntensity::Dull) => "#[fg=magenta]",
-            (BaseColor::Cyan, ColorIntensity::Dull) => "#[fg=cyan]",
-            (BaseColor::White, ColorIntensity::Dull) => "#[fg=white]",
-            (BaseColor::NoColor, _) => "#[fg=default]",
+    /// The default color without any attributes.
+    pub fn is_plain(&self) -> bool {
+        matches!(self.color, TermColor::Named(BaseColor::NoColor))
+            && matches!(self.background, TermColor::Named(BaseColor::NoColor))
+            && !(self.bold || self.dim || self.italic || self.underline)
+    }
+
+    fn is_vivid(&self) -> bool {
+        matches!(self.intensity, ColorIntensity::Vivid)
+    }
+
+    fn attributes(&self, bold: bool) -> impl Iterator<Item = (&'static str, &'static str)> {
+        [
+            (bold, "1", "bold"),
+            (self.dim, "2", "dim"),
+            (self.italic, "3", "italics"),
+            (self.underline, "4", "underscore"),
+        ]
+        .into_iter()
+        .filter(|(enabled, _, _)| *enabled)
+        .map(|(_, sgr, tmux)| (sgr, tmux))
+    }
+
+    pub fn terminal_start_code(&self) -> String {
+        let mut parameters = Vec::new();
+        let foreground = self.color.sgr_parameters(30);
+        // Without a color all attributes are reset first, vivid only makes
+        // colored text bold.
+        if foreground.is_none() {
+            parameters.push("0".to_string());
         }
+        let bold = self.bold || (self.is_vivid() && foreground.is_some());
+        parameters.extend(self.attributes(bold).map(|(sgr, _)| sgr.to_string()));
+        parameters.push(foreground.unwrap_or_else(|| "39".into()));
+        parameters.extend(self.background.sgr_parameters(40));
+
+        format!("\x1b[{}m", parameters.join(";"))
+    }
+
+    pub fn tmux_start_code(&self) -> String {
+        let mut styles = vec![format!(
+            "fg={}",
+            self.color
+                .tmux_name(self.intensity)
+                .unwrap_or_else(|| "default".into())
+        )];
+        styles.extend(
+            self.background
+                .tmux_name(ColorIntensity::Dull)
+                .map(|background| format!("bg={background}")),
+        );
+        styles.extend(self.attributes(self.bold).map(|(_, tmux)| tmux.to_string()));
+
+        format!("#[{}]", styles.join(","))
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// A text shown in the given color.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
 pub struct ColoredTag {
     #[serde(flatten)]
     pub color: Color,
+    /// The text to show.
     pub tag: String,
 }
 
-#[derive(ValueEnum, Clone, Copy, Debug)]
+/// A tag shown after a count, switching to the tag of the highest threshold
+/// reached by the count.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct CountTag {
+    #[serde(flatten)]
+    pub tag: ColoredTag,
+    /// Tags for counts of at least `min`, given values override the ones of
+    /// this tag.
+    pub thresholds: Vec<Threshold>,
+}
+
+impl CountTag {
+    pub fn for_count(&self, count: usize) -> &ColoredTag {
+        self.thresholds
+            .iter()
+            .filter(|threshold| count >= threshold.min)
+            .max_by_key(|threshold| threshold.min)
+            .map_or(&self.tag, |threshold| &threshold.tag)
+    }
+}
+
+impl From<ColoredTag> for CountTag {
+    fn from(tag: ColoredTag) -> Self {
+        Self {
+            tag,
+            thresholds: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct Threshold {
+    /// The lowest count this tag is used for.
+    pub min: usize,
+    #[serde(flatten)]
+    pub tag: ColoredTag,
+}
+
+/// The output target of the prompt.
+#[derive(
+    ValueEnum,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+)]
 #[clap(rename_all = "lower")]
+#[serde(rename_all = "lowercase")]
 pub enum Shell {
     Bash,
     Zsh,
@@ -92,3 +293,88 @@ pub enum Shell {
     None,
     Other,
 }
+
+impl Shell {
+    pub fn name(self) -> String {
+        self.to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(value: &str) -> Result<RgbColor, String> {
+        RgbColor::try_from(value.to_string())
+    }
+
+    fn tag(tag: &str) -> ColoredTag {
+        ColoredTag {
+            color: Color::new(BaseColor::Green, ColorIntensity::Vivid),
+            tag: tag.into(),
+        }
+    }
+
+    #[test]
+    fn parses_rgb_colors() {
+        let color = rgb("#FF8000").unwrap();
+
+        assert_eq!((color.0, color.1, color.2), (255, 128, 0));
+        assert_eq!(color.to_string(), "#ff8000");
+    }
+
+    #[test]
+    fn rejects_invalid_rgb_colors() {
+        for value in ["ff8000", "#ff800", "#ff80000", "#gg8000", "#ff80é"] {
+            assert_eq!(
+                rgb(value).unwrap_err(),
+                format!("invalid color `{value}`, expected \"#rrggbb\"")
+            );
+        }
+    }
+
+    #[test]
+    fn count_tag_uses_the_highest_reached_threshold() {
+        let count_tag = CountTag {
+            tag: tag("a"),
+            thresholds: vec![
+                Threshold {
+                    min: 10,
+                    tag: tag("c"),
+                },
+                Threshold {
+                    min: 3,
+                    tag: tag("b"),
+                },
+            ],
+        };
+        let tag_for = |count| count_tag.for_count(count).tag.as_str();
+
+        assert_eq!(tag_for(1), "a");
+        assert_eq!(tag_for(3), "b");
+        assert_eq!(tag_for(9), "b");
+        assert_eq!(tag_for(10), "c");
+        assert_eq!(tag_for(100), "c");
+    }
+
+    #[test]
+    fn terminal_codes() {
+        let mut color = Color::new(BaseColor::Red, ColorIntensity::Vivid);
+        assert_eq!(color.terminal_start_code(), "\x1b[1;31m");
+
+        color.color = TermColor::Indexed(208);
+        color.background = TermColor::Rgb(RgbColor(0x30, 0x30, 0x30));
+        color.intensity = ColorIntensity::Dull;
+        color.italic = true;
+        assert_eq!(
+            color.terminal_start_code(),
+            "\x1b[3;38;5;208;48;2;48;48;48m"
+        );
+        assert_eq!(
+            color.tmux_start_code(),
+            "#[fg=colour208,bg=#303030,italics]"
+        );
+    }
+}